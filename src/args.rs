@@ -1,11 +1,19 @@
-use crate::{generator, util};
+use crate::{expression, path_util, util};
 use ffmpeg4::Rational;
+use julia_in_motion::{generator, output};
+use lyon_path::math::point;
+use num_complex::Complex;
 use std::{
+    collections::{HashMap, HashSet},
+    env,
+    ffi::OsString,
     fmt::{Display, Error, Formatter},
-    fs::create_dir_all,
+    fs::{create_dir_all, read, read_to_string},
     io,
     num::{ParseFloatError, ParseIntError},
+    ops::Range,
     path::{Path, PathBuf},
+    str::FromStr,
     time::Duration,
 };
 
@@ -13,25 +21,126 @@ pub struct CmdArgs {
     pub image_width: u32,
     pub image_height: u32,
     pub plane_width: f64,
+    pub plane_height: Option<f64>,
+    pub center: Complex<f64>,
+    pub rotation: f64,
+    pub zoom: Option<(f64, f64)>,
     pub frames: u32,
-    pub path: lyon_path::Path,
+    /// The frames that are actually rendered, for splitting a render up or
+    /// resuming one.
+    pub frame_range: Range<u32>,
+    /// The number of frames along the path, which is less than `frames` when
+    /// the path is played back in reverse after it.
+    pub path_frames: u32,
+    pub boomerang: Option<BoomerangMode>,
+    pub fade_in: u32,
+    pub fade_out: u32,
+    pub c_path: CPath,
+    /// The argument and value the path of `c` was given by, like
+    /// `--circle 0,0,0.5`.
+    pub c_path_source: String,
+    pub seamless_loop: bool,
     pub output: PathBuf,
+    pub output_distance: Option<PathBuf>,
     pub iterations: u32,
+    /// The iterations to ramp between from the first frame to the last, if
+    /// any.
+    pub iteration_ramp: Option<(u32, u32)>,
     pub fractal_progress_interval: Duration,
     pub video_progress_interval: Duration,
+    pub progress_format: ProgressFormat,
+    pub fps: Rational,
     pub time_base: Rational,
+    pub quality: output::VideoQuality,
+    pub encode_threads: Option<u32>,
+    pub codec: Option<String>,
+    pub pixel_format: Option<String>,
+    pub encoder_options: Vec<(String, String)>,
+    pub format: Option<String>,
+    /// Whether the frames are written as raw RGBA bytes instead of a video.
+    pub raw: bool,
     pub path_tolerance: f32,
     pub smoothing: generator::args::Smoothing,
-    pub mandelbrot: bool,
+    pub escape_radius: f64,
+    pub supersample: u32,
+    pub downsample: u32,
+    pub threads: usize,
+    pub parallel_frames: usize,
+    /// The most memory in bytes that frame buffers may take up, if limited.
+    pub max_memory: Option<u64>,
+    pub pipeline: bool,
+    pub period_check: bool,
+    pub bulb_check: bool,
+    pub coloring: generator::args::ColoringMode,
+    pub tone_map: generator::args::ToneMap,
+    pub palette: Option<generator::palette::Palette>,
+    pub palette_period: Option<f64>,
+    pub interior_color: generator::RGBAColor,
+    pub interior_coloring: generator::args::InteriorColoring,
+    pub trap_center: Complex<f64>,
+    pub trap_radius: f64,
+    pub font: Option<Vec<u8>>,
+    pub hue_scale: f64,
+    pub brightness_scale: f64,
+    pub gamma: f64,
+    pub fractal_type: generator::args::FractalType,
+    pub precision: generator::args::Precision,
+    pub power: u32,
+    pub crosshair_color: generator::RGBAColor,
+    pub crosshair_thickness: u32,
+    pub antialias_crosshair: bool,
+    pub marker: Marker,
+    pub marker_radius: f64,
+    /// The template for the coordinate label, or `None` if it is hidden.
+    pub label_format: Option<String>,
+    pub label_size: f32,
+    pub title: Option<String>,
+    pub title_duration: f64,
+    pub end_card: Option<String>,
+    pub end_card_duration: f64,
+    pub card_color: generator::RGBAColor,
+    pub card_text_size: f32,
+    pub minimap: bool,
+    pub split: bool,
+    pub path_overlay: bool,
+    pub orbit_trail: bool,
+    pub orbit_seed: Complex<f64>,
+    pub orbit_color: generator::RGBAColor,
+    pub allow_partial_frames: bool,
+    pub stats_overlay: bool,
+    pub frame_number_overlay: bool,
+    pub write_metadata: bool,
+    pub dry_run: bool,
+    pub palette_preview: Option<PathBuf>,
+    pub benchmark: bool,
+    /// The number of bits per color channel, which is 8 unless a 16-bit still
+    /// image was asked for.
+    pub bit_depth: u32,
+    /// The most detailed level of log messages that are shown.
+    pub log_level: log::LevelFilter,
 }
 
 impl CmdArgs {
     pub fn load() -> Result<CmdArgs, CmdArgsLoadError> {
         // load up option parser
         let options_yaml = clap::load_yaml!("options.yml");
+
+        // options from a config file go before the command line ones, leaving out any the
+        // command line overrides
+        let mut args: Vec<OsString> = env::args_os().collect();
+        if let Some(config_file) = find_config_file(&args) {
+            let options = config_options();
+            let given = given_options(&args, &options);
+
+            let config_args = parse_config(&read_to_string(&config_file)?, &options, &given)
+                .map_err(|e| CmdArgsLoadError::from_config("config", e))?;
+            args.splice(1..1, config_args);
+        }
+
         let matches = clap::App::from_yaml(options_yaml)
             .version(clap::crate_version!())
-            .get_matches();
+            .setting(clap::AppSettings::AllArgsOverrideSelf)
+            .get_matches_from(args);
 
         // parse all the options
         let image_width = matches
@@ -44,30 +153,159 @@ impl CmdArgs {
             .unwrap()
             .parse::<u32>()
             .map_err(|e| CmdArgsLoadError::from_int("image-height", e))?;
-        let frames = matches
-            .value_of("frames")
-            .unwrap()
-            .parse::<u32>()
-            .map_err(|e| CmdArgsLoadError::from_int("frames", e))?;
-        let plane_width = matches
-            .value_of("plane_width")
+
+        let center = match matches.value_of("center") {
+            Some(center) => util::parse_complex(center)
+                .map_err(|e| CmdArgsLoadError::from_complex("center", e))?,
+            None => Complex::<f64>::new(0f64, 0f64),
+        };
+
+        let rotation = matches
+            .value_of("rotation")
             .unwrap()
             .parse::<f64>()
-            .map_err(|e| CmdArgsLoadError::from_float("plane-width", e))?;
+            .map_err(|e| CmdArgsLoadError::from_float("rotation", e))?;
+
+        // get the plane widths to zoom between, if any
+        let zoom = match (matches.value_of("zoom_start"), matches.value_of("zoom_end")) {
+            (Some(start), Some(end)) => {
+                let start = start
+                    .parse::<f64>()
+                    .map_err(|e| CmdArgsLoadError::from_float("zoom-start", e))?;
+                let end = end
+                    .parse::<f64>()
+                    .map_err(|e| CmdArgsLoadError::from_float("zoom-end", e))?;
+                if !(start.is_finite() && end.is_finite()) || start <= 0f64 || end <= 0f64 {
+                    return Err(CmdArgsLoadError::invalid(
+                        "zoom-start",
+                        "zoom widths must be positive numbers",
+                    ));
+                }
+                Some((start, end))
+            }
+            _ => None,
+        };
+
+        // the plane width defaults to the start of the zoom
+        let plane_width = match (matches.value_of("plane_width"), zoom) {
+            (Some(plane_width), _) => plane_width
+                .parse::<f64>()
+                .map_err(|e| CmdArgsLoadError::from_float("plane-width", e))?,
+            (None, Some((start, _))) => start,
+            (None, None) => unreachable!("clap requires --plane-width without a zoom"),
+        };
+
+        // the plane height keeps the pixels square unless it is given explicitly
+        let plane_height = match matches.value_of("plane_height") {
+            Some(plane_height) => {
+                let plane_height = plane_height
+                    .parse::<f64>()
+                    .map_err(|e| CmdArgsLoadError::from_float("plane-height", e))?;
+                if !(plane_height.is_finite() && plane_height > 0f64) {
+                    return Err(CmdArgsLoadError::invalid(
+                        "plane-height",
+                        "must be positive",
+                    ));
+                }
+                Some(plane_height)
+            }
+            None => None,
+        };
 
-        // parse the output file and create its parent directories if needed
+        // previews and dry runs don't render anything, so they leave the file system alone
+        let renders = !matches.is_present("palette_preview") && !matches.is_present("dry_run");
+
+        // parse the output file and create its parent directories if needed, unless it's stdout
+        // or a benchmark, which renders without writing any frames
         let output = Path::new(matches.value_of("output").unwrap());
-        if let Some(parent) = output.parent() {
+        let to_stdout = output::MediaOutput::is_stdout_path(output);
+        let writes_output = renders && !to_stdout && !matches.is_present("benchmark");
+        if let Some(parent) = output.parent().filter(|_| writes_output) {
             if !parent.exists() {
                 create_dir_all(parent)?;
             }
         }
 
-        // parse the path string as an SVG path
-        let path_str = matches.value_of("path").unwrap();
-        let svg_builder = lyon_path::Path::builder().with_svg();
-        let path = lyon_svg::path_utils::build_path(svg_builder, path_str)
-            .map_err(|e| CmdArgsLoadError::from_path("path", e))?;
+        // parse the optional distance estimate output file
+        let output_distance = matches.value_of("output_distance").map(PathBuf::from);
+        if let Some(parent) = output_distance
+            .as_ref()
+            .and_then(|p| p.parent())
+            .filter(|_| renders)
+        {
+            if !parent.exists() {
+                create_dir_all(parent)?;
+            }
+        }
+
+        // parse the single value of c for a still image, the parametric c function, or the path
+        // string as an SVG path
+        let c_path = if let Some(still) = matches.value_of("still") {
+            CPath::Point(
+                util::parse_complex(still)
+                    .map_err(|e| CmdArgsLoadError::from_complex("still", e))?,
+            )
+        } else if let Some(circle) = matches.value_of("circle") {
+            let values = parse_floats("circle", circle, 3)?;
+            CPath::Svg(path_util::circle_path(
+                point(values[0], values[1]),
+                values[2],
+            ))
+        } else if let Some(spiral) = matches.value_of("spiral") {
+            let values = parse_floats("spiral", spiral, 5)?;
+            CPath::Svg(path_util::spiral_path(
+                point(values[0], values[1]),
+                values[2],
+                values[3],
+                values[4],
+            ))
+        } else if let Some(points) = matches.value_of("points") {
+            CPath::Svg(parse_points("points", points)?)
+        } else if let Some(points_file) = matches.value_of("points_file") {
+            CPath::Svg(parse_points("points-file", &read_to_string(points_file)?)?)
+        } else if let Some(c_function) = matches.value_of("c_function") {
+            CPath::Function(
+                c_function
+                    .parse::<expression::Expression>()
+                    .map_err(|e| CmdArgsLoadError::from_expression("c-function", e))?,
+            )
+        } else {
+            let path_str = matches.value_of("path").unwrap();
+            let svg_builder = lyon_path::Path::builder().with_svg();
+            CPath::Svg(
+                lyon_svg::path_utils::build_path(svg_builder, path_str)
+                    .map_err(|e| CmdArgsLoadError::from_path("path", e))?,
+            )
+        };
+
+        // remember the argument the path came from so the render can be described later
+        let c_path_source = [
+            "still",
+            "circle",
+            "spiral",
+            "points",
+            "points_file",
+            "c_function",
+            "path",
+        ]
+        .iter()
+        .find_map(|&name| {
+            matches
+                .value_of(name)
+                .map(|value| format!("--{} {}", name.replace('_', "-"), value))
+        })
+        .unwrap_or_default();
+
+        // looping only makes sense for paths that end where they start
+        let seamless_loop = matches.is_present("loop");
+        if let CPath::Svg(path) = &c_path {
+            if seamless_loop && !path_util::is_closed(path.as_slice()) {
+                return Err(CmdArgsLoadError::invalid(
+                    "loop",
+                    "the path must be closed with a Z command",
+                ));
+            }
+        }
 
         // get the optional arguments
         let iterations = matches
@@ -75,6 +313,27 @@ impl CmdArgs {
             .unwrap()
             .parse::<u32>()
             .map_err(|e| CmdArgsLoadError::from_int("iterations", e))?;
+        let iteration_ramp = match (
+            matches.value_of("iterations_start"),
+            matches.value_of("iterations_end"),
+        ) {
+            (Some(start), Some(end)) => {
+                let start = start
+                    .parse::<u32>()
+                    .map_err(|e| CmdArgsLoadError::from_int("iterations-start", e))?;
+                let end = end
+                    .parse::<u32>()
+                    .map_err(|e| CmdArgsLoadError::from_int("iterations-end", e))?;
+                if start == 0 || end == 0 {
+                    return Err(CmdArgsLoadError::invalid(
+                        "iterations-start",
+                        "iteration counts must be at least 1",
+                    ));
+                }
+                Some((start, end))
+            }
+            _ => None,
+        };
         let fractal_progress_interval = Duration::from_millis(
             matches
                 .value_of("fractal_progress_interval")
@@ -89,8 +348,174 @@ impl CmdArgs {
                 .parse::<u64>()
                 .map_err(|e| CmdArgsLoadError::from_int("video-progress-interval", e))?,
         );
-        let time_base = util::parse_rational(matches.value_of("time_base").unwrap())
-            .map_err(|e| CmdArgsLoadError::from_rational("time-base", e))?;
+        let progress_format = matches
+            .value_of("progress_format")
+            .unwrap()
+            .parse::<ProgressFormat>()
+            .map_err(|e| CmdArgsLoadError::from_progress_format("progress-format", e))?;
+        let fps = util::parse_rational(matches.value_of("fps").unwrap())
+            .map_err(|e| CmdArgsLoadError::from_rational("fps", e))?;
+        if fps.numerator() <= 0 || fps.denominator() <= 0 {
+            return Err(CmdArgsLoadError::invalid("fps", "must be a positive rate"));
+        }
+
+        // get the number of frames directly or from the duration, a still image only has one
+        let frames = if let Some(frames) = matches.value_of("frames") {
+            frames
+                .parse::<u32>()
+                .map_err(|e| CmdArgsLoadError::from_int("frames", e))?
+        } else if let Some(duration) = matches.value_of("duration") {
+            let duration = duration
+                .parse::<f64>()
+                .map_err(|e| CmdArgsLoadError::from_float("duration", e))?;
+            frames_for_duration(duration, fps)?
+        } else {
+            1
+        };
+
+        // a boomerang plays the path forward and then back again, without repeating either end
+        let path_frames = frames;
+        let boomerang = if matches.is_present("boomerang") {
+            Some(
+                matches
+                    .value_of("boomerang_mode")
+                    .unwrap()
+                    .parse::<BoomerangMode>()
+                    .map_err(|e| CmdArgsLoadError::from_boomerang_mode("boomerang-mode", e))?,
+            )
+        } else {
+            None
+        };
+        let frames = if boomerang.is_some() {
+            if path_frames < 2 {
+                return Err(CmdArgsLoadError::invalid(
+                    "boomerang",
+                    "the path needs at least 2 frames",
+                ));
+            }
+            path_frames * 2 - 2
+        } else {
+            frames
+        };
+
+        // only render part of the video if asked, still numbering frames from the start
+        let start_frame = match matches.value_of("start_frame") {
+            Some(start_frame) => start_frame
+                .parse::<u32>()
+                .map_err(|e| CmdArgsLoadError::from_int("start-frame", e))?,
+            None => 0,
+        };
+        let end_frame = match matches.value_of("end_frame") {
+            Some(end_frame) => end_frame
+                .parse::<u32>()
+                .map_err(|e| CmdArgsLoadError::from_int("end-frame", e))?,
+            None => frames,
+        };
+        if end_frame > frames {
+            return Err(CmdArgsLoadError::invalid(
+                "end-frame",
+                "must not be past the last frame",
+            ));
+        }
+        if start_frame >= end_frame {
+            return Err(CmdArgsLoadError::invalid(
+                "start-frame",
+                "must be before the end frame",
+            ));
+        }
+        let frame_range = start_frame..end_frame;
+        if boomerang == Some(BoomerangMode::Buffer) && frame_range.len() != frames as usize {
+            return Err(CmdArgsLoadError::invalid(
+                "boomerang-mode",
+                "buffering needs the whole video, so it can't be used with --start-frame or --end-frame",
+            ));
+        }
+
+        // fades are counted in frames of the whole video, not just the rendered range
+        let fade_in = match matches.value_of("fade_in") {
+            Some(fade_in) => fade_in
+                .parse::<u32>()
+                .map_err(|e| CmdArgsLoadError::from_int("fade-in", e))?,
+            None => 0,
+        };
+        let fade_out = match matches.value_of("fade_out") {
+            Some(fade_out) => fade_out
+                .parse::<u32>()
+                .map_err(|e| CmdArgsLoadError::from_int("fade-out", e))?,
+            None => 0,
+        };
+
+        // keep the time base consistent with the frame rate unless it is given explicitly
+        let time_base = match matches.value_of("time_base") {
+            Some(time_base) => util::parse_rational(time_base)
+                .map_err(|e| CmdArgsLoadError::from_rational("time-base", e))?,
+            None => fps.invert(),
+        };
+        if time_base.numerator() <= 0 || time_base.denominator() <= 0 {
+            return Err(CmdArgsLoadError::invalid("time-base", "must be positive"));
+        }
+        // timestamps are counted in time base units, so each frame has to last a whole number of them
+        if (fps.denominator() as i64 * time_base.denominator() as i64)
+            % (fps.numerator() as i64 * time_base.numerator() as i64)
+            != 0
+        {
+            return Err(CmdArgsLoadError::invalid(
+                "time-base",
+                "must divide the duration of a frame evenly",
+            ));
+        }
+
+        // get the quality the video should be encoded at, defaulting to a crf of 30
+        let quality = if let Some(bitrate) = matches.value_of("bitrate") {
+            output::VideoQuality::Bitrate(
+                bitrate
+                    .parse::<usize>()
+                    .map_err(|e| CmdArgsLoadError::from_int("bitrate", e))?,
+            )
+        } else {
+            output::VideoQuality::Crf(
+                matches
+                    .value_of("crf")
+                    .unwrap_or("30")
+                    .parse::<u32>()
+                    .map_err(|e| CmdArgsLoadError::from_int("crf", e))?,
+            )
+        };
+
+        // get the number of threads the encoder should use, leaving it up to ffmpeg if unspecified
+        let encode_threads = match matches.value_of("encode_threads") {
+            Some(threads) => Some(
+                threads
+                    .parse::<u32>()
+                    .map_err(|e| CmdArgsLoadError::from_int("encode-threads", e))?,
+            ),
+            None => None,
+        };
+
+        // get the encoder and pixel format, which are validated when the output is opened
+        let codec = matches.value_of("codec").map(str::to_owned);
+        let pixel_format = matches.value_of("pixel_format").map(str::to_owned);
+        let format = matches.value_of("format").map(str::to_owned);
+        let raw = matches.is_present("raw") || output::RawOutput::is_raw_path(output);
+
+        // get any extra encoder options, which are also validated when the output is opened
+        let encoder_options = match matches.values_of("encoder_option") {
+            Some(options) => options
+                .map(|option| {
+                    let mut parts = option.splitn(2, '=');
+                    match (parts.next(), parts.next()) {
+                        (Some(name), Some(value)) if !name.is_empty() => {
+                            Ok((name.to_owned(), value.to_owned()))
+                        }
+                        _ => Err(CmdArgsLoadError::invalid(
+                            "encoder-option",
+                            "expected KEY=VALUE",
+                        )),
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => vec![],
+        };
 
         // get the path tolerance
         let path_tolerance = matches
@@ -98,6 +523,29 @@ impl CmdArgs {
             .unwrap()
             .parse::<f32>()
             .map_err(|e| CmdArgsLoadError::from_float("path-tolerance", e))?;
+        if !(path_tolerance.is_finite() && path_tolerance > 0f32) {
+            return Err(CmdArgsLoadError::invalid(
+                "path-tolerance",
+                "must be positive",
+            ));
+        }
+
+        // a path without any points has nowhere to put the frames, and one with an infinite
+        // length can't be split into them
+        if let CPath::Svg(path) = &c_path {
+            if path.iter().next().is_none() {
+                return Err(CmdArgsLoadError::invalid(
+                    "path",
+                    "must contain at least one point",
+                ));
+            }
+            if !path_util::approximate_path_length(path.as_slice(), path_tolerance).is_finite() {
+                return Err(CmdArgsLoadError::invalid(
+                    "path",
+                    "must have a finite length",
+                ));
+            }
+        }
 
         // get the kind of smoothing to use
         let smoothing = matches
@@ -106,27 +554,914 @@ impl CmdArgs {
             .parse::<generator::args::Smoothing>()
             .map_err(|e| CmdArgsLoadError::from_smoothing("smoothing", e))?;
 
+        // get the escape radius, defaulting to the one the smoothing was designed for
+        let escape_radius = match matches.value_of("escape_radius") {
+            Some(escape_radius) => escape_radius
+                .parse::<f64>()
+                .map_err(|e| CmdArgsLoadError::from_float("escape-radius", e))?,
+            None => smoothing.radius_squared().sqrt(),
+        };
+        if !escape_radius.is_finite() || escape_radius <= 0f64 {
+            return Err(CmdArgsLoadError::invalid(
+                "escape-radius",
+                "must be a positive number",
+            ));
+        }
+
+        // get how many samples to take along each axis of every pixel
+        let supersample = matches
+            .value_of("supersample")
+            .unwrap()
+            .parse::<u32>()
+            .map_err(|e| CmdArgsLoadError::from_int("supersample", e))?;
+        if supersample < 1 {
+            return Err(CmdArgsLoadError::invalid(
+                "supersample",
+                "must be at least 1",
+            ));
+        }
+
+        // get how many times larger each frame is rendered before it is shrunk
+        let downsample = matches
+            .value_of("downsample")
+            .unwrap()
+            .parse::<u32>()
+            .map_err(|e| CmdArgsLoadError::from_int("downsample", e))?;
+        if downsample < 1 {
+            return Err(CmdArgsLoadError::invalid(
+                "downsample",
+                "must be at least 1",
+            ));
+        }
+
+        // get how many fractal threads to use, where 0 picks a number from the CPU count
+        let threads = matches
+            .value_of("threads")
+            .unwrap()
+            .parse::<usize>()
+            .map_err(|e| CmdArgsLoadError::from_int("threads", e))?;
+
+        let parallel_frames = matches
+            .value_of("parallel_frames")
+            .unwrap()
+            .parse::<usize>()
+            .map_err(|e| CmdArgsLoadError::from_int("parallel-frames", e))?;
+        if parallel_frames < 1 {
+            return Err(CmdArgsLoadError::invalid(
+                "parallel-frames",
+                "must be at least 1",
+            ));
+        }
+
+        // get the memory limit, which is given in megabytes
+        let max_memory = match matches.value_of("max_memory") {
+            Some(max_memory) => Some(
+                max_memory
+                    .parse::<u64>()
+                    .map_err(|e| CmdArgsLoadError::from_int("max-memory", e))?
+                    .saturating_mul(1024 * 1024),
+            ),
+            None => None,
+        };
+
+        let pipeline = matches.is_present("pipeline");
+
+        let period_check = !matches.is_present("no_period_check");
+        let bulb_check = !matches.is_present("no_bulb_check");
+
+        // get how values should be mapped to colors
+        let coloring = match matches.value_of("orbit_trap") {
+            // an orbit trap implies orbit-trap coloring
+            Some(orbit_trap) => generator::args::ColoringMode::OrbitTrap(
+                orbit_trap
+                    .parse::<generator::args::OrbitTrap>()
+                    .map_err(|e| CmdArgsLoadError::from_orbit_trap("orbit-trap", e))?,
+            ),
+            None => matches
+                .value_of("coloring")
+                .unwrap()
+                .parse::<generator::args::ColoringMode>()
+                .map_err(|e| CmdArgsLoadError::from_coloring_mode("coloring", e))?,
+        };
+
+        // get how colors should be tone mapped
+        let tone_map = matches
+            .value_of("tone_map")
+            .unwrap()
+            .parse::<generator::args::ToneMap>()
+            .map_err(|e| CmdArgsLoadError::from_tone_map("tone-map", e))?;
+
+        // load the font file if there is one, otherwise the embedded font is used
+        let font = match matches.value_of("font") {
+            Some(font_file) => Some(read(font_file)?),
+            None => None,
+        };
+
+        // load the palette file if there is one
+        let palette = match matches.value_of("palette") {
+            Some(palette_file) => Some(
+                read_to_string(palette_file)?
+                    .parse::<generator::palette::Palette>()
+                    .map_err(|e| CmdArgsLoadError::from_palette("palette", e))?,
+            ),
+            None => None,
+        };
+
+        // get how many iterations the colors repeat over, if any
+        let palette_period = match matches.value_of("palette_period") {
+            Some(period) => {
+                let period = period
+                    .parse::<f64>()
+                    .map_err(|e| CmdArgsLoadError::from_float("palette-period", e))?;
+                if !(period.is_finite() && period > 0f64) {
+                    return Err(CmdArgsLoadError::invalid(
+                        "palette-period",
+                        "must be positive",
+                    ));
+                }
+                Some(period)
+            }
+            None => None,
+        };
+
+        // an explicit interior color takes precedence over the palette's
+        let interior_color = match matches.value_of("interior_color") {
+            Some(color) => util::parse_color(color)
+                .map_err(|e| CmdArgsLoadError::from_color("interior-color", e))?,
+            None => palette.as_ref().map_or_else(
+                || generator::RGBAColor::new(0, 0, 0, 255),
+                |palette| palette.interior_color(),
+            ),
+        };
+
+        let interior_coloring = matches
+            .value_of("interior_coloring")
+            .unwrap()
+            .parse::<generator::args::InteriorColoring>()
+            .map_err(|e| CmdArgsLoadError::from_interior_coloring("interior-coloring", e))?;
+
+        // get where the orbit trap is placed
+        let trap_center = util::parse_complex(matches.value_of("trap_center").unwrap())
+            .map_err(|e| CmdArgsLoadError::from_complex("trap-center", e))?;
+        let trap_radius = matches
+            .value_of("trap_radius")
+            .unwrap()
+            .parse::<f64>()
+            .map_err(|e| CmdArgsLoadError::from_float("trap-radius", e))?;
+        if !(trap_radius.is_finite() && trap_radius >= 0f64) {
+            return Err(CmdArgsLoadError::invalid(
+                "trap-radius",
+                "must not be negative",
+            ));
+        }
+
+        // get the color cycling speeds
+        let hue_scale = matches
+            .value_of("hue_scale")
+            .unwrap()
+            .parse::<f64>()
+            .map_err(|e| CmdArgsLoadError::from_float("hue-scale", e))?;
+        let brightness_scale = matches
+            .value_of("brightness_scale")
+            .unwrap()
+            .parse::<f64>()
+            .map_err(|e| CmdArgsLoadError::from_float("brightness-scale", e))?;
+
+        let gamma = matches
+            .value_of("gamma")
+            .unwrap()
+            .parse::<f64>()
+            .map_err(|e| CmdArgsLoadError::from_float("gamma", e))?;
+        if !(gamma.is_finite() && gamma > 0f64) {
+            return Err(CmdArgsLoadError::invalid("gamma", "must be positive"));
+        }
+
+        // get the kind of fractal to generate, where --mandelbrot is a shorthand for
+        // --fractal mandelbrot
+        let fractal_type = if matches.is_present("mandelbrot") {
+            generator::args::FractalType::Mandelbrot
+        } else {
+            matches
+                .value_of("fractal")
+                .unwrap()
+                .parse::<generator::args::FractalType>()
+                .map_err(|e| CmdArgsLoadError::from_fractal_type("fractal", e))?
+        };
+        if !fractal_type.has_distance_estimate() {
+            if coloring == generator::args::ColoringMode::Distance {
+                return Err(CmdArgsLoadError::invalid(
+                    "coloring",
+                    "distance coloring is not supported for burning-ship, tricorn, or newton fractals",
+                ));
+            }
+            if output_distance.is_some() {
+                return Err(CmdArgsLoadError::invalid(
+                    "output-distance",
+                    "distance images are not supported for burning-ship, tricorn, or newton fractals",
+                ));
+            }
+        }
+        // palettes are only tone mapped where the tone map gives their position
+        if palette.is_some()
+            && tone_map != generator::args::ToneMap::Clamp
+            && coloring != generator::args::ColoringMode::Distance
+            && fractal_type != generator::args::FractalType::Newton
+        {
+            return Err(CmdArgsLoadError::invalid(
+                "tone-map",
+                "only applies to a --palette with distance coloring or newton fractals",
+            ));
+        }
+
+        // get the precision the escape loop is calculated in
+        let precision = matches
+            .value_of("precision")
+            .unwrap()
+            .parse::<generator::args::Precision>()
+            .map_err(|e| CmdArgsLoadError::from_precision("precision", e))?;
+
+        // get the power z is raised to each iteration
+        let power = matches
+            .value_of("power")
+            .unwrap()
+            .parse::<u32>()
+            .map_err(|e| CmdArgsLoadError::from_int("power", e))?;
+        if power < 2 {
+            return Err(CmdArgsLoadError::invalid("power", "must be at least 2"));
+        }
+
+        let crosshair_color = util::parse_color(matches.value_of("crosshair_color").unwrap())
+            .map_err(|e| CmdArgsLoadError::from_color("crosshair-color", e))?;
+        let crosshair_thickness = matches
+            .value_of("crosshair_thickness")
+            .unwrap()
+            .parse::<u32>()
+            .map_err(|e| CmdArgsLoadError::from_int("crosshair-thickness", e))?;
+        if crosshair_thickness < 1 {
+            return Err(CmdArgsLoadError::invalid(
+                "crosshair-thickness",
+                "must be at least 1",
+            ));
+        }
+
+        let antialias_crosshair = matches.is_present("antialias_crosshair");
+
+        // get how the current value of c is marked
+        let marker = matches
+            .value_of("marker")
+            .unwrap()
+            .parse::<Marker>()
+            .map_err(|e| CmdArgsLoadError::from_marker("marker", e))?;
+        let marker_radius = matches
+            .value_of("marker_radius")
+            .unwrap()
+            .parse::<f64>()
+            .map_err(|e| CmdArgsLoadError::from_float("marker-radius", e))?;
+        if !marker_radius.is_finite() || marker_radius <= 0f64 {
+            return Err(CmdArgsLoadError::invalid(
+                "marker-radius",
+                "must be a positive number of pixels",
+            ));
+        }
+
+        let label_format = if matches.is_present("no_label") {
+            None
+        } else {
+            Some(matches.value_of("label_format").unwrap().to_owned())
+        };
+        let label_size = matches
+            .value_of("label_size")
+            .unwrap()
+            .parse::<f32>()
+            .map_err(|e| CmdArgsLoadError::from_float("label-size", e))?;
+        if !(label_size.is_finite() && label_size > 0f32) {
+            return Err(CmdArgsLoadError::invalid("label-size", "must be positive"));
+        }
+
+        // get the title and end cards shown before and after the video
+        let title = matches.value_of("title").map(str::to_owned);
+        let title_duration = matches
+            .value_of("title_duration")
+            .unwrap()
+            .parse::<f64>()
+            .map_err(|e| CmdArgsLoadError::from_float("title-duration", e))?;
+        if !title_duration.is_finite() || title_duration < 0f64 {
+            return Err(CmdArgsLoadError::invalid(
+                "title-duration",
+                "must be a non-negative number of seconds",
+            ));
+        }
+        let end_card = matches.value_of("end_card").map(str::to_owned);
+        let end_card_duration = matches
+            .value_of("end_card_duration")
+            .unwrap()
+            .parse::<f64>()
+            .map_err(|e| CmdArgsLoadError::from_float("end-card-duration", e))?;
+        if !end_card_duration.is_finite() || end_card_duration < 0f64 {
+            return Err(CmdArgsLoadError::invalid(
+                "end-card-duration",
+                "must be a non-negative number of seconds",
+            ));
+        }
+        let card_color = util::parse_color(matches.value_of("card_color").unwrap())
+            .map_err(|e| CmdArgsLoadError::from_color("card-color", e))?;
+        let card_text_size = matches
+            .value_of("card_text_size")
+            .unwrap()
+            .parse::<f32>()
+            .map_err(|e| CmdArgsLoadError::from_float("card-text-size", e))?;
+        if !(card_text_size.is_finite() && card_text_size > 0f32) {
+            return Err(CmdArgsLoadError::invalid(
+                "card-text-size",
+                "must be positive",
+            ));
+        }
+
         // get the flags
-        let mandelbrot = matches.is_present("mandelbrot");
+        let minimap = matches.is_present("minimap");
+        let split = matches.is_present("split");
+        let path_overlay = matches.is_present("path_overlay");
+
+        // get where orbit trails start on Julia sets and what color they are drawn in
+        let orbit_trail = matches.is_present("orbit_trail");
+        let orbit_seed = util::parse_complex(matches.value_of("orbit_seed").unwrap())
+            .map_err(|e| CmdArgsLoadError::from_complex("orbit-seed", e))?;
+        let orbit_color = util::parse_color(matches.value_of("orbit_color").unwrap())
+            .map_err(|e| CmdArgsLoadError::from_color("orbit-color", e))?;
+        let allow_partial_frames = matches.is_present("allow_partial_frames");
+        let stats_overlay = matches.is_present("stats_overlay");
+        let write_metadata = matches.is_present("write_metadata");
+        let dry_run = matches.is_present("dry_run");
+        let palette_preview = matches.value_of("palette_preview").map(PathBuf::from);
+        let benchmark = matches.is_present("benchmark");
+
+        // only still images can have more than 8 bits per channel, as videos are
+        // encoded from 8-bit frames
+        let bit_depth = matches
+            .value_of("bit_depth")
+            .unwrap()
+            .parse::<u32>()
+            .map_err(|e| CmdArgsLoadError::from_int("bit-depth", e))?;
+        if bit_depth != 8 && bit_depth != 16 {
+            return Err(CmdArgsLoadError::invalid("bit-depth", "must be 8 or 16"));
+        }
+        if bit_depth == 16 && !matches.is_present("still") {
+            return Err(CmdArgsLoadError::invalid(
+                "bit-depth",
+                "16 bits per channel is only supported for --still images",
+            ));
+        }
+        if bit_depth == 16 && downsample > 1 {
+            return Err(CmdArgsLoadError::invalid(
+                "downsample",
+                "isn't supported with 16 bits per channel, use --supersample instead",
+            ));
+        }
+
+        // only a video can be streamed to stdout, and nothing else may be printed there
+        if to_stdout {
+            if matches.is_present("still") {
+                return Err(CmdArgsLoadError::invalid(
+                    "output",
+                    "still images can't be written to stdout",
+                ));
+            }
+            if format.is_none() && !raw {
+                return Err(CmdArgsLoadError::invalid(
+                    "output",
+                    "writing to stdout requires a container --format or --raw",
+                ));
+            }
+            if progress_format == ProgressFormat::Json {
+                return Err(CmdArgsLoadError::invalid(
+                    "progress-format",
+                    "JSON progress can't be printed to stdout while the video is written there",
+                ));
+            }
+            if write_metadata || benchmark || dry_run {
+                return Err(CmdArgsLoadError::invalid(
+                    "output",
+                    "stdout can't be used with --write-metadata, --benchmark, or --dry-run",
+                ));
+            }
+        }
+
+        // warnings are shown by default, and each -v shows more detail
+        let log_level = if matches.is_present("quiet") {
+            log::LevelFilter::Error
+        } else {
+            match matches.occurrences_of("verbose") {
+                0 => log::LevelFilter::Warn,
+                1 => log::LevelFilter::Info,
+                2 => log::LevelFilter::Debug,
+                _ => log::LevelFilter::Trace,
+            }
+        };
+        let frame_number_overlay = matches.is_present("overlay_frame_number");
 
         Ok(CmdArgs {
             image_width,
             image_height,
             plane_width,
+            plane_height,
+            center,
+            rotation,
+            zoom,
             frames,
-            path,
+            frame_range,
+            path_frames,
+            boomerang,
+            fade_in,
+            fade_out,
+            c_path,
+            c_path_source,
+            seamless_loop,
             output: output.to_path_buf(),
+            output_distance,
             iterations,
+            iteration_ramp,
             fractal_progress_interval,
             video_progress_interval,
+            progress_format,
+            fps,
             time_base,
+            quality,
+            encode_threads,
+            codec,
+            pixel_format,
+            encoder_options,
+            format,
+            raw,
             path_tolerance,
             smoothing,
-            mandelbrot,
+            escape_radius,
+            supersample,
+            downsample,
+            threads,
+            parallel_frames,
+            max_memory,
+            pipeline,
+            period_check,
+            bulb_check,
+            coloring,
+            tone_map,
+            palette,
+            palette_period,
+            interior_color,
+            interior_coloring,
+            trap_center,
+            trap_radius,
+            font,
+            hue_scale,
+            brightness_scale,
+            gamma,
+            fractal_type,
+            precision,
+            power,
+            crosshair_color,
+            crosshair_thickness,
+            antialias_crosshair,
+            marker,
+            marker_radius,
+            label_format,
+            label_size,
+            title,
+            title_duration,
+            end_card,
+            end_card_duration,
+            card_color,
+            card_text_size,
+            minimap,
+            split,
+            path_overlay,
+            orbit_trail,
+            orbit_seed,
+            orbit_color,
+            allow_partial_frames,
+            stats_overlay,
+            frame_number_overlay,
+            write_metadata,
+            dry_run,
+            palette_preview,
+            benchmark,
+            bit_depth,
+            log_level,
         })
     }
 }
 
+/// Parses a list of exactly `count` comma-separated numbers.
+fn parse_floats(argument: &str, string: &str, count: usize) -> Result<Vec<f32>, CmdArgsLoadError> {
+    let values = string
+        .split(',')
+        .map(|value| value.trim().parse::<f32>())
+        .collect::<Result<Vec<f32>, ParseFloatError>>()
+        .map_err(|e| CmdArgsLoadError::from_float(argument, e))?;
+
+    if values.len() != count {
+        return Err(CmdArgsLoadError::invalid(
+            argument,
+            "wrong number of comma-separated values",
+        ));
+    }
+
+    Ok(values)
+}
+
+/// Parses a list of complex numbers separated by semicolons or newlines into a
+/// path with straight lines between them.
+fn parse_points(argument: &str, string: &str) -> Result<lyon_path::Path, CmdArgsLoadError> {
+    let points = string
+        .split(|c| c == ';' || c == '\n')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .parse::<expression::Expression>()
+                .map(|expression| {
+                    let c = expression.evaluate(0f64);
+                    point(c.re as f32, c.im as f32)
+                })
+                .map_err(|e| CmdArgsLoadError::from_expression(argument, e))
+        })
+        .collect::<Result<Vec<_>, CmdArgsLoadError>>()?;
+
+    if points.is_empty() {
+        return Err(CmdArgsLoadError::invalid(
+            argument,
+            "must contain at least one point",
+        ));
+    }
+
+    Ok(path_util::polyline_path(&points))
+}
+
+/// Gets the number of frames a `--duration` in seconds lasts at a frame rate,
+/// which has to be at least one.
+fn frames_for_duration(duration: f64, fps: Rational) -> Result<u32, CmdArgsLoadError> {
+    if !duration.is_finite() || duration <= 0f64 {
+        return Err(CmdArgsLoadError::invalid(
+            "duration",
+            "must be a positive number of seconds",
+        ));
+    }
+
+    let frames = (duration * fps.numerator() as f64 / fps.denominator() as f64).round() as u32;
+    if frames == 0 {
+        return Err(CmdArgsLoadError::invalid(
+            "duration",
+            "must last at least one frame",
+        ));
+    }
+
+    Ok(frames)
+}
+
+/// Checks whether `--list-codecs` was given. This is checked before the
+/// arguments are parsed, so the options a render requires can be left out.
+pub fn list_codecs_requested() -> bool {
+    env::args_os().skip(1).any(|arg| arg == "--list-codecs")
+}
+
+/// Finds the value of the `--config` argument, if there is one.
+fn find_config_file(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter().skip(1);
+
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        } else if let Some(value) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(value));
+        }
+    }
+
+    None
+}
+
+/// An option described in options.yml, with what is needed to find it on the
+/// command line.
+struct ConfigOption {
+    long: String,
+    short: Option<char>,
+    takes_value: bool,
+    /// The names of the options this one can't be given with.
+    conflicts: Vec<String>,
+}
+
+impl ConfigOption {
+    fn conflicts_with(&self, name: &str) -> bool {
+        self.conflicts.iter().any(|conflict| conflict == name)
+    }
+}
+
+/// Gets every option by name, like `image_width`.
+fn config_options() -> HashMap<String, ConfigOption> {
+    let options_yaml = clap::load_yaml!("options.yml");
+
+    let mut options = HashMap::new();
+    for arg in options_yaml["args"].as_vec().unwrap() {
+        for (name, settings) in arg.as_hash().unwrap() {
+            if let (Some(name), Some(long)) = (name.as_str(), settings["long"].as_str()) {
+                // conflicts are either a single name or a list of them
+                let conflicts = match settings["conflicts_with"].as_vec() {
+                    Some(conflicts) => conflicts
+                        .iter()
+                        .filter_map(|conflict| conflict.as_str())
+                        .map(str::to_owned)
+                        .collect(),
+                    None => settings["conflicts_with"]
+                        .as_str()
+                        .map(str::to_owned)
+                        .into_iter()
+                        .collect(),
+                };
+
+                options.insert(
+                    name.to_owned(),
+                    ConfigOption {
+                        long: long.to_owned(),
+                        short: settings["short"].as_str().and_then(|s| s.chars().next()),
+                        takes_value: settings["takes_value"].as_bool().unwrap_or(false),
+                        conflicts,
+                    },
+                );
+            }
+        }
+    }
+
+    options
+}
+
+/// Finds the names of the options given on the command line.
+fn given_options(args: &[OsString], options: &HashMap<String, ConfigOption>) -> HashSet<String> {
+    let mut given = HashSet::new();
+    let mut args = args.iter().skip(1);
+
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if let Some(long) = arg.strip_prefix("--") {
+            let (long, has_value) = match long.find('=') {
+                Some(index) => (&long[..index], true),
+                None => (long, false),
+            };
+
+            if let Some((name, option)) = options.iter().find(|(_, option)| option.long == long) {
+                given.insert(name.clone());
+                if option.takes_value && !has_value {
+                    args.next();
+                }
+            }
+        } else if let Some(shorts) = arg.strip_prefix('-') {
+            // short flags can be grouped like -vv, up to one that takes a value
+            for (index, short) in shorts.char_indices() {
+                let (name, option) = match options
+                    .iter()
+                    .find(|(_, option)| option.short == Some(short))
+                {
+                    Some(found) => found,
+                    None => break,
+                };
+
+                given.insert(name.clone());
+                if option.takes_value {
+                    if index + short.len_utf8() == shorts.len() {
+                        args.next();
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    given
+}
+
+/// Turns a TOML config file into command line arguments. Keys are the names of
+/// options, like `image_width`, and arrays of numbers are joined with commas
+/// the way options like `--center` expect. Flags are set with `true`. Options
+/// that were `given` on the command line are left out, along with any that
+/// can't be given with them, so the command line overrides the file.
+fn parse_config(
+    config: &str,
+    options: &HashMap<String, ConfigOption>,
+    given: &HashSet<String>,
+) -> Result<Vec<OsString>, ParseConfigError> {
+    let table = match config.parse::<toml::Value>()? {
+        toml::Value::Table(table) => table,
+        _ => return Err(ParseConfigError::NotATable),
+    };
+
+    let mut args = vec![];
+    for (key, value) in table {
+        let name = key.replace('-', "_");
+        let option = options
+            .get(&name)
+            .filter(|option| option.long != "config")
+            .ok_or_else(|| ParseConfigError::UnknownOption(key.clone()))?;
+        let long = &option.long;
+
+        // conflicts are only listed on one side, so check both
+        let overridden = given.iter().any(|given| {
+            *given == name
+                || option.conflicts_with(given)
+                || options
+                    .get(given)
+                    .map_or(false, |given| given.conflicts_with(&name))
+        });
+        if overridden {
+            continue;
+        }
+
+        let value = match value {
+            toml::Value::Boolean(true) => {
+                args.push(format!("--{}", long).into());
+                continue;
+            }
+            toml::Value::Boolean(false) => continue,
+            toml::Value::String(value) => value,
+            toml::Value::Integer(value) => value.to_string(),
+            toml::Value::Float(value) => value.to_string(),
+            toml::Value::Array(values) => values
+                .iter()
+                .map(|value| match value {
+                    toml::Value::Integer(value) => Ok(value.to_string()),
+                    toml::Value::Float(value) => Ok(value.to_string()),
+                    _ => Err(ParseConfigError::UnsupportedValue(key.clone())),
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .join(","),
+            _ => return Err(ParseConfigError::UnsupportedValue(key)),
+        };
+
+        args.push(format!("--{}={}", long, value).into());
+    }
+
+    Ok(args)
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseConfigError {
+    NotATable,
+    UnknownOption(String),
+    UnsupportedValue(String),
+    TomlError(toml::de::Error),
+}
+
+impl Display for ParseConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            ParseConfigError::NotATable => write!(f, "the config must be a table of options"),
+            ParseConfigError::UnknownOption(key) => write!(f, "unknown option '{}'", key),
+            ParseConfigError::UnsupportedValue(key) => {
+                write!(f, "option '{}' has an unsupported kind of value", key)
+            }
+            ParseConfigError::TomlError(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ParseConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseConfigError::TomlError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<toml::de::Error> for ParseConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        ParseConfigError::TomlError(e)
+    }
+}
+
+/// How progress is reported while the video is generated.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ProgressFormat {
+    /// A progress bar for people watching the terminal.
+    Human,
+    /// One JSON object per line on stdout for other programs to parse.
+    Json,
+}
+
+impl FromStr for ProgressFormat {
+    type Err = ParseProgressFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "human" => Ok(ProgressFormat::Human),
+            "json" => Ok(ProgressFormat::Json),
+            _ => Err(ParseProgressFormatError::NotProgressFormat),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseProgressFormatError {
+    NotProgressFormat,
+}
+
+impl Display for ParseProgressFormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "expected human or json")
+    }
+}
+
+impl std::error::Error for ParseProgressFormatError {}
+
+/// What marks the current value of `c` on Mandelbrot videos.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Marker {
+    /// Lines across the whole image that cross at `c`.
+    Crosshair,
+    /// A small filled circle at `c`.
+    Dot,
+    /// Both the crosshair and the dot.
+    Both,
+}
+
+impl Marker {
+    pub fn has_crosshair(&self) -> bool {
+        *self != Marker::Dot
+    }
+
+    pub fn has_dot(&self) -> bool {
+        *self != Marker::Crosshair
+    }
+}
+
+impl FromStr for Marker {
+    type Err = ParseMarkerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "crosshair" => Ok(Marker::Crosshair),
+            "dot" => Ok(Marker::Dot),
+            "both" => Ok(Marker::Both),
+            _ => Err(ParseMarkerError::NotMarker),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseMarkerError {
+    NotMarker,
+}
+
+impl Display for ParseMarkerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "expected crosshair, dot, or both")
+    }
+}
+
+impl std::error::Error for ParseMarkerError {}
+
+/// How the reversed half of a boomerang is made.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BoomerangMode {
+    /// Renders every frame again, which doubles the generation time.
+    Rerender,
+    /// Keeps the frames rendered going forward in memory and writes them
+    /// again in reverse.
+    Buffer,
+}
+
+impl FromStr for BoomerangMode {
+    type Err = ParseBoomerangModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "rerender" => Ok(BoomerangMode::Rerender),
+            "buffer" => Ok(BoomerangMode::Buffer),
+            _ => Err(ParseBoomerangModeError::NotBoomerangMode),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseBoomerangModeError {
+    NotBoomerangMode,
+}
+
+impl Display for ParseBoomerangModeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "expected rerender or buffer")
+    }
+}
+
+impl std::error::Error for ParseBoomerangModeError {}
+
+/// Describes where the value of `c` comes from for each frame.
+pub enum CPath {
+    /// An SVG path on the complex plane that is walked at regular intervals.
+    Svg(lyon_path::Path),
+    /// A parametric function of `t`, which goes from 0 to 1 over the video.
+    Function(expression::Expression),
+    /// A single value of `c`, rendered as a still image.
+    Point(Complex<f64>),
+}
+
 #[derive(Debug)]
 pub enum CmdArgsLoadError {
     IOError(io::Error),
@@ -134,6 +1469,10 @@ pub enum CmdArgsLoadError {
         argument: String,
         cause: ParseErrorCause,
     },
+    InvalidValue {
+        argument: String,
+        reason: &'static str,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -141,11 +1480,76 @@ pub enum ParseErrorCause {
     ParseFloatError(ParseFloatError),
     ParseIntError(ParseIntError),
     ParsePathError(lyon_svg::path_utils::ParseError),
+    ParseExpressionError(expression::ParseExpressionError),
     ParseRationalError(util::ParseRationalError),
+    ParseComplexError(util::ParseComplexError),
+    ParseColorError(util::ParseColorError),
     ParseSmoothingError(generator::args::ParseSmoothingError),
+    ParseFractalTypeError(generator::args::ParseFractalTypeError),
+    ParsePrecisionError(generator::args::ParsePrecisionError),
+    ParseInteriorColoringError(generator::args::ParseInteriorColoringError),
+    ParsePaletteError(generator::palette::ParsePaletteError),
+    ParseToneMapError(generator::args::ParseToneMapError),
+    ParseColoringModeError(generator::args::ParseColoringModeError),
+    ParseOrbitTrapError(generator::args::ParseOrbitTrapError),
+    ParseProgressFormatError(ParseProgressFormatError),
+    ParseMarkerError(ParseMarkerError),
+    ParseBoomerangModeError(ParseBoomerangModeError),
+    ParseConfigError(ParseConfigError),
+}
+
+impl ParseErrorCause {
+    /// Gets the underlying error, which is every cause but an SVG path error.
+    fn error(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseErrorCause::ParseFloatError(e) => Some(e),
+            ParseErrorCause::ParseIntError(e) => Some(e),
+            ParseErrorCause::ParsePathError(_) => None,
+            ParseErrorCause::ParseExpressionError(e) => Some(e),
+            ParseErrorCause::ParseRationalError(e) => Some(e),
+            ParseErrorCause::ParseComplexError(e) => Some(e),
+            ParseErrorCause::ParseColorError(e) => Some(e),
+            ParseErrorCause::ParseSmoothingError(e) => Some(e),
+            ParseErrorCause::ParseFractalTypeError(e) => Some(e),
+            ParseErrorCause::ParsePrecisionError(e) => Some(e),
+            ParseErrorCause::ParseInteriorColoringError(e) => Some(e),
+            ParseErrorCause::ParsePaletteError(e) => Some(e),
+            ParseErrorCause::ParseToneMapError(e) => Some(e),
+            ParseErrorCause::ParseColoringModeError(e) => Some(e),
+            ParseErrorCause::ParseOrbitTrapError(e) => Some(e),
+            ParseErrorCause::ParseProgressFormatError(e) => Some(e),
+            ParseErrorCause::ParseMarkerError(e) => Some(e),
+            ParseErrorCause::ParseBoomerangModeError(e) => Some(e),
+            ParseErrorCause::ParseConfigError(e) => Some(e),
+        }
+    }
+}
+
+impl Display for ParseErrorCause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            // lyon's path errors can only be debug printed
+            ParseErrorCause::ParsePathError(e) => write!(f, "invalid SVG path ({:?})", e),
+            _ => self.error().map_or(Ok(()), |e| write!(f, "{}", e)),
+        }
+    }
+}
+
+impl std::error::Error for ParseErrorCause {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        // this only wraps the underlying error, so skip straight to its cause
+        self.error().and_then(|e| e.source())
+    }
 }
 
 impl CmdArgsLoadError {
+    pub fn invalid(argument: &str, reason: &'static str) -> CmdArgsLoadError {
+        CmdArgsLoadError::InvalidValue {
+            argument: argument.to_owned(),
+            reason,
+        }
+    }
+
     pub fn from_float(argument: &str, error: ParseFloatError) -> CmdArgsLoadError {
         CmdArgsLoadError::ParseError {
             argument: argument.to_owned(),
@@ -167,6 +1571,30 @@ impl CmdArgsLoadError {
         }
     }
 
+    pub fn from_expression(
+        argument: &str,
+        error: expression::ParseExpressionError,
+    ) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParseExpressionError(error),
+        }
+    }
+
+    pub fn from_complex(argument: &str, error: util::ParseComplexError) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParseComplexError(error),
+        }
+    }
+
+    pub fn from_color(argument: &str, error: util::ParseColorError) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParseColorError(error),
+        }
+    }
+
     pub fn from_rational(argument: &str, error: util::ParseRationalError) -> CmdArgsLoadError {
         CmdArgsLoadError::ParseError {
             argument: argument.to_owned(),
@@ -183,15 +1611,130 @@ impl CmdArgsLoadError {
             cause: ParseErrorCause::ParseSmoothingError(error),
         }
     }
+
+    pub fn from_fractal_type(
+        argument: &str,
+        error: generator::args::ParseFractalTypeError,
+    ) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParseFractalTypeError(error),
+        }
+    }
+
+    pub fn from_interior_coloring(
+        argument: &str,
+        error: generator::args::ParseInteriorColoringError,
+    ) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParseInteriorColoringError(error),
+        }
+    }
+
+    pub fn from_precision(
+        argument: &str,
+        error: generator::args::ParsePrecisionError,
+    ) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParsePrecisionError(error),
+        }
+    }
+
+    pub fn from_palette(
+        argument: &str,
+        error: generator::palette::ParsePaletteError,
+    ) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParsePaletteError(error),
+        }
+    }
+
+    pub fn from_tone_map(
+        argument: &str,
+        error: generator::args::ParseToneMapError,
+    ) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParseToneMapError(error),
+        }
+    }
+
+    pub fn from_coloring_mode(
+        argument: &str,
+        error: generator::args::ParseColoringModeError,
+    ) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParseColoringModeError(error),
+        }
+    }
+
+    pub fn from_orbit_trap(
+        argument: &str,
+        error: generator::args::ParseOrbitTrapError,
+    ) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParseOrbitTrapError(error),
+        }
+    }
+
+    pub fn from_progress_format(
+        argument: &str,
+        error: ParseProgressFormatError,
+    ) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParseProgressFormatError(error),
+        }
+    }
+
+    pub fn from_marker(argument: &str, error: ParseMarkerError) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParseMarkerError(error),
+        }
+    }
+
+    pub fn from_boomerang_mode(argument: &str, error: ParseBoomerangModeError) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParseBoomerangModeError(error),
+        }
+    }
+
+    pub fn from_config(argument: &str, error: ParseConfigError) -> CmdArgsLoadError {
+        CmdArgsLoadError::ParseError {
+            argument: argument.to_owned(),
+            cause: ParseErrorCause::ParseConfigError(error),
+        }
+    }
 }
 
 impl Display for CmdArgsLoadError {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         match self {
-            CmdArgsLoadError::ParseError { argument, .. } => {
-                f.write_fmt(format_args!("Unable to parse --{} argument", argument))
+            CmdArgsLoadError::ParseError { argument, cause } => f.write_fmt(format_args!(
+                "Unable to parse --{} argument: {}",
+                argument, cause
+            )),
+            CmdArgsLoadError::InvalidValue { argument, reason } => {
+                f.write_fmt(format_args!("Invalid --{} argument: {}", argument, reason))
             }
-            CmdArgsLoadError::IOError(_) => f.write_str("IO Error"),
+            CmdArgsLoadError::IOError(e) => f.write_fmt(format_args!("IO Error: {}", e)),
+        }
+    }
+}
+
+impl std::error::Error for CmdArgsLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CmdArgsLoadError::IOError(e) => Some(e),
+            CmdArgsLoadError::ParseError { cause, .. } => Some(cause),
+            CmdArgsLoadError::InvalidValue { .. } => None,
         }
     }
 }
@@ -201,3 +1744,109 @@ impl From<io::Error> for CmdArgsLoadError {
         CmdArgsLoadError::IOError(e)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os_args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    fn config_args(config: &str, args: &[&str]) -> Vec<OsString> {
+        let options = config_options();
+        parse_config(config, &options, &given_options(&os_args(args), &options)).unwrap()
+    }
+
+    #[test]
+    fn duration_is_counted_in_frames() {
+        let fps = Rational::new(30, 1);
+
+        assert_eq!(frames_for_duration(2f64, fps).unwrap(), 60);
+        assert_eq!(frames_for_duration(0.02f64, fps).unwrap(), 1);
+    }
+
+    #[test]
+    fn duration_shorter_than_a_frame_is_rejected() {
+        let error = frames_for_duration(0.01f64, Rational::new(30, 1)).unwrap_err();
+
+        assert!(matches!(
+            error,
+            CmdArgsLoadError::InvalidValue { ref argument, .. } if argument == "duration"
+        ));
+    }
+
+    #[test]
+    fn command_line_overrides_config_file() {
+        let args = config_args(
+            "image_width = 100\ncrf = 20",
+            &["julia-in-motion", "-w", "200"],
+        );
+
+        assert_eq!(args, os_args(&["--crf=20"]));
+    }
+
+    #[test]
+    fn command_line_overrides_conflicting_config_options() {
+        let config =
+            "frames = 60\ncrf = 20\nverbose = true\npath = \"M 0 0 L 1 1\"\nimage_width = 100";
+        let args = config_args(
+            config,
+            &[
+                "julia-in-motion",
+                "--duration",
+                "2",
+                "--bitrate=1000000",
+                "-q",
+                "--circle",
+                "0,0,1",
+            ],
+        );
+
+        assert_eq!(args, os_args(&["--image-width=100"]));
+    }
+
+    #[test]
+    fn grouped_short_options_are_found() {
+        let args = config_args(
+            "image_width = 100\nimage_height = 50\nquiet = true",
+            &["julia-in-motion", "-vw200"],
+        );
+
+        assert_eq!(args, os_args(&["--image-height=50"]));
+    }
+
+    #[test]
+    fn overridden_config_file_parses_with_command_line() {
+        let config = "image_width = 100\nframes = 60\ncrf = 20\nverbose = true\npath = \"M 0 0 L 1 1\"\noutput = \"out.mp4\"\nplane_width = 4";
+        let mut args = os_args(&[
+            "julia-in-motion",
+            "-h",
+            "50",
+            "--duration",
+            "2",
+            "--bitrate",
+            "1000000",
+            "-q",
+            "--circle",
+            "0,0,1",
+        ]);
+        let options = config_options();
+        let given = given_options(&args, &options);
+        args.splice(1..1, parse_config(config, &options, &given).unwrap());
+
+        let matches = clap::App::from_yaml(clap::load_yaml!("options.yml"))
+            .setting(clap::AppSettings::AllArgsOverrideSelf)
+            .get_matches_from_safe(args)
+            .unwrap();
+
+        assert_eq!(matches.value_of("image_width"), Some("100"));
+        assert_eq!(matches.value_of("duration"), Some("2"));
+        assert_eq!(matches.value_of("frames"), None);
+        assert_eq!(matches.value_of("bitrate"), Some("1000000"));
+        assert_eq!(matches.value_of("crf"), None);
+        assert!(matches.is_present("quiet"));
+        assert!(!matches.is_present("verbose"));
+        assert_eq!(matches.value_of("path"), None);
+    }
+}
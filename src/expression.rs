@@ -0,0 +1,401 @@
+use num_complex::Complex;
+use std::{
+    error::Error,
+    f64::consts,
+    fmt::{self, Display, Formatter},
+    iter::Peekable,
+    num::ParseFloatError,
+    str::{Chars, FromStr},
+};
+
+/// A parsed mathematical expression over the complex numbers with a single
+/// real variable `t`.
+///
+/// Expressions support `+`, `-`, `*`, `/`, `^`, parentheses, implicit
+/// multiplication (`2pi`, `3(t + 1)`), the constants `i`, `pi`, and `e`, and
+/// the functions `exp`, `ln`, `sqrt`, `sin`, `cos`, `tan`, `abs`, `arg`,
+/// `conj`, `re`, and `im`.
+#[derive(Debug, Clone)]
+pub enum Expression {
+    Constant(Complex<f64>),
+    Variable,
+    Negate(Box<Expression>),
+    Add(Box<Expression>, Box<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+    Multiply(Box<Expression>, Box<Expression>),
+    Divide(Box<Expression>, Box<Expression>),
+    Power(Box<Expression>, Box<Expression>),
+    Function(Function, Box<Expression>),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Function {
+    Exp,
+    Ln,
+    Sqrt,
+    Sin,
+    Cos,
+    Tan,
+    Abs,
+    Arg,
+    Conj,
+    Re,
+    Im,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    LeftParen,
+    RightParen,
+}
+
+impl Expression {
+    /// Evaluates this expression with the variable `t` set to the given value.
+    pub fn evaluate(&self, t: f64) -> Complex<f64> {
+        match self {
+            Expression::Constant(value) => *value,
+            Expression::Variable => Complex::<f64>::new(t, 0f64),
+            Expression::Negate(a) => -a.evaluate(t),
+            Expression::Add(a, b) => a.evaluate(t) + b.evaluate(t),
+            Expression::Subtract(a, b) => a.evaluate(t) - b.evaluate(t),
+            Expression::Multiply(a, b) => a.evaluate(t) * b.evaluate(t),
+            Expression::Divide(a, b) => a.evaluate(t) / b.evaluate(t),
+            Expression::Power(a, b) => a.evaluate(t).powc(b.evaluate(t)),
+            Expression::Function(function, a) => function.apply(a.evaluate(t)),
+        }
+    }
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "exp" => Some(Function::Exp),
+            "ln" | "log" => Some(Function::Ln),
+            "sqrt" => Some(Function::Sqrt),
+            "sin" => Some(Function::Sin),
+            "cos" => Some(Function::Cos),
+            "tan" => Some(Function::Tan),
+            "abs" => Some(Function::Abs),
+            "arg" => Some(Function::Arg),
+            "conj" => Some(Function::Conj),
+            "re" => Some(Function::Re),
+            "im" => Some(Function::Im),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, value: Complex<f64>) -> Complex<f64> {
+        match self {
+            Function::Exp => value.exp(),
+            Function::Ln => value.ln(),
+            Function::Sqrt => value.sqrt(),
+            Function::Sin => value.sin(),
+            Function::Cos => value.cos(),
+            Function::Tan => value.tan(),
+            Function::Abs => Complex::<f64>::new(value.norm(), 0f64),
+            Function::Arg => Complex::<f64>::new(value.arg(), 0f64),
+            Function::Conj => value.conj(),
+            Function::Re => Complex::<f64>::new(value.re, 0f64),
+            Function::Im => Complex::<f64>::new(value.im, 0f64),
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = ParseExpressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(&mut s.chars().peekable())?;
+        let mut parser = Parser { tokens, index: 0 };
+
+        let expression = parser.parse_sum()?;
+
+        match parser.peek() {
+            None => Ok(expression),
+            Some(token) => Err(ParseExpressionError::UnexpectedToken(token.to_string())),
+        }
+    }
+}
+
+fn tokenize(chars: &mut Peekable<Chars>) -> Result<Vec<Token>, ParseExpressionError> {
+    let mut tokens = vec![];
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_ascii_digit() || c == '.' {
+                    number.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Number(number.parse::<f64>()?));
+        } else if c.is_ascii_alphabetic() {
+            let mut identifier = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_ascii_alphabetic() {
+                    identifier.push(c.to_ascii_lowercase());
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Identifier(identifier));
+        } else {
+            tokens.push(match c {
+                '+' => Token::Plus,
+                '-' => Token::Minus,
+                '*' => Token::Star,
+                '/' => Token::Slash,
+                '^' => Token::Caret,
+                '(' => Token::LeftParen,
+                ')' => Token::RightParen,
+                _ => return Err(ParseExpressionError::UnexpectedCharacter(c)),
+            });
+            chars.next();
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseExpressionError> {
+        match self.advance() {
+            Some(ref token) if *token == expected => Ok(()),
+            Some(token) => Err(ParseExpressionError::UnexpectedToken(token.to_string())),
+            None => Err(ParseExpressionError::UnexpectedEnd),
+        }
+    }
+
+    /// sum := product (('+' | '-') product)*
+    fn parse_sum(&mut self) -> Result<Expression, ParseExpressionError> {
+        let mut expression = self.parse_product()?;
+
+        loop {
+            match self.peek() {
+                Some(Token::Plus) => {
+                    self.advance();
+                    expression =
+                        Expression::Add(Box::new(expression), Box::new(self.parse_product()?));
+                }
+                Some(Token::Minus) => {
+                    self.advance();
+                    expression =
+                        Expression::Subtract(Box::new(expression), Box::new(self.parse_product()?));
+                }
+                _ => return Ok(expression),
+            }
+        }
+    }
+
+    /// product := unary (('*' | '/')? unary)*
+    fn parse_product(&mut self) -> Result<Expression, ParseExpressionError> {
+        let mut expression = self.parse_unary()?;
+
+        loop {
+            match self.peek() {
+                Some(Token::Star) => {
+                    self.advance();
+                    expression =
+                        Expression::Multiply(Box::new(expression), Box::new(self.parse_unary()?));
+                }
+                Some(Token::Slash) => {
+                    self.advance();
+                    expression =
+                        Expression::Divide(Box::new(expression), Box::new(self.parse_unary()?));
+                }
+                // implicit multiplication, like `2pi` or `3(t + 1)`
+                Some(Token::Number(_)) | Some(Token::Identifier(_)) | Some(Token::LeftParen) => {
+                    expression =
+                        Expression::Multiply(Box::new(expression), Box::new(self.parse_power()?));
+                }
+                _ => return Ok(expression),
+            }
+        }
+    }
+
+    /// unary := '-' unary | power
+    fn parse_unary(&mut self) -> Result<Expression, ParseExpressionError> {
+        if let Some(Token::Minus) = self.peek() {
+            self.advance();
+            Ok(Expression::Negate(Box::new(self.parse_unary()?)))
+        } else {
+            self.parse_power()
+        }
+    }
+
+    /// power := primary ('^' unary)?
+    fn parse_power(&mut self) -> Result<Expression, ParseExpressionError> {
+        let base = self.parse_primary()?;
+
+        if let Some(Token::Caret) = self.peek() {
+            self.advance();
+            Ok(Expression::Power(
+                Box::new(base),
+                Box::new(self.parse_unary()?),
+            ))
+        } else {
+            Ok(base)
+        }
+    }
+
+    /// primary := number | constant | 't' | function '(' sum ')' | '(' sum ')'
+    fn parse_primary(&mut self) -> Result<Expression, ParseExpressionError> {
+        match self.advance() {
+            Some(Token::Number(value)) => {
+                Ok(Expression::Constant(Complex::<f64>::new(value, 0f64)))
+            }
+            Some(Token::Identifier(name)) => match name.as_str() {
+                "t" => Ok(Expression::Variable),
+                "i" => Ok(Expression::Constant(Complex::<f64>::new(0f64, 1f64))),
+                "pi" => Ok(Expression::Constant(Complex::<f64>::new(consts::PI, 0f64))),
+                "e" => Ok(Expression::Constant(Complex::<f64>::new(consts::E, 0f64))),
+                _ => {
+                    let function = Function::from_name(&name)
+                        .ok_or(ParseExpressionError::UnknownIdentifier(name))?;
+                    self.expect(Token::LeftParen)?;
+                    let argument = self.parse_sum()?;
+                    self.expect(Token::RightParen)?;
+                    Ok(Expression::Function(function, Box::new(argument)))
+                }
+            },
+            Some(Token::LeftParen) => {
+                let expression = self.parse_sum()?;
+                self.expect(Token::RightParen)?;
+                Ok(expression)
+            }
+            Some(token) => Err(ParseExpressionError::UnexpectedToken(token.to_string())),
+            None => Err(ParseExpressionError::UnexpectedEnd),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseExpressionError {
+    UnexpectedCharacter(char),
+    UnexpectedToken(String),
+    UnexpectedEnd,
+    UnknownIdentifier(String),
+    ParseFloatError(ParseFloatError),
+}
+
+impl From<ParseFloatError> for ParseExpressionError {
+    fn from(e: ParseFloatError) -> Self {
+        ParseExpressionError::ParseFloatError(e)
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(number) => write!(f, "{}", number),
+            Token::Identifier(name) => write!(f, "{}", name),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Star => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::Caret => write!(f, "^"),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+        }
+    }
+}
+
+impl Display for ParseExpressionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseExpressionError::UnexpectedCharacter(c) => {
+                write!(f, "unexpected character '{}'", c)
+            }
+            ParseExpressionError::UnexpectedToken(token) => write!(f, "unexpected '{}'", token),
+            ParseExpressionError::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            ParseExpressionError::UnknownIdentifier(name) => {
+                write!(f, "unknown constant or function '{}'", name)
+            }
+            ParseExpressionError::ParseFloatError(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for ParseExpressionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseExpressionError::ParseFloatError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Complex<f64>, expected: Complex<f64>) {
+        assert!(
+            (actual - expected).norm() < 1e-12f64,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn parametric_circle_matches_hand_computed_values() {
+        let expression = "0.7885 * exp(i * t * 2pi)".parse::<Expression>().unwrap();
+
+        // e^0 = 1 and e^(i pi) = -1
+        assert_close(
+            expression.evaluate(0f64),
+            Complex::<f64>::new(0.7885f64, 0f64),
+        );
+        assert_close(
+            expression.evaluate(0.5f64),
+            Complex::<f64>::new(-0.7885f64, 0f64),
+        );
+    }
+
+    #[test]
+    fn operators_follow_precedence() {
+        let expression = "1 + 2t^2 - re(3 - 4i) / 2".parse::<Expression>().unwrap();
+
+        assert_close(expression.evaluate(0.5f64), Complex::<f64>::new(0f64, 0f64));
+        assert_close(expression.evaluate(1f64), Complex::<f64>::new(1.5f64, 0f64));
+    }
+
+    #[test]
+    fn malformed_expressions_describe_the_problem() {
+        let message = |s: &str| s.parse::<Expression>().unwrap_err().to_string();
+
+        assert_eq!(message("1 +"), "unexpected end of expression");
+        assert_eq!(message("2 * )"), "unexpected ')'");
+        assert_eq!(message("foo(t)"), "unknown constant or function 'foo'");
+        assert_eq!(message("1 $ 2"), "unexpected character '$'");
+    }
+}
@@ -1,6 +1,13 @@
+use super::{palette, IterationResult, RGBAColor};
 use num_complex::Complex;
 use regex::{Regex, RegexBuilder};
-use std::{num::ParseFloatError, str::FromStr};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    num::ParseFloatError,
+    str::FromStr,
+};
 
 lazy_static::lazy_static! {
 static ref SMOOTHING_REGEX: Regex = RegexBuilder::new(r"^logarithmic(distance)? *\( *(?P<radius>\d+(\.\d+)?|\.\d+) *, *(?P<max_power>\d+(\.\d+)?|\.\d+)\)$").case_insensitive(true).build().unwrap();
@@ -9,7 +16,138 @@ static ref SMOOTHING_REGEX: Regex = RegexBuilder::new(r"^logarithmic(distance)?
 const DEFAULT_RADIUS: f64 = 4f64;
 const DEFAULT_RADIUS_SQUARED: f64 = DEFAULT_RADIUS * DEFAULT_RADIUS;
 
-#[derive(Debug, Copy, Clone)]
+/// Parameters describing which fractal is generated and how the iterations of
+/// each pixel are evaluated.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct FractalParams {
+    pub fractal_type: FractalType,
+    pub power: u32,
+    pub iterations: u32,
+    pub smoothing: Smoothing,
+    pub escape_radius: f64,
+    /// The number of samples taken along each axis of every pixel.
+    pub supersample: u32,
+    /// Whether orbits that repeat are stopped early, as they never escape.
+    pub period_check: bool,
+    /// Whether points in the main cardioid or period-2 bulb of the Mandelbrot
+    /// set are skipped without being iterated, as they never escape.
+    pub bulb_check: bool,
+    /// The precision the escape loop is calculated in.
+    pub precision: Precision,
+}
+
+/// Parameters controlling how the values generated for each pixel are turned
+/// into colors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColoringParams {
+    pub mode: ColoringMode,
+    pub tone_map: ToneMap,
+    pub palette: Option<palette::Palette>,
+    /// The number of iterations in one full cycle of the palette or hue, if
+    /// the colors should repeat at a fixed period instead of following the
+    /// scales.
+    pub palette_period: Option<f64>,
+    /// The color of points that never escape.
+    pub interior_color: RGBAColor,
+    /// How points that never escape are colored.
+    pub interior_coloring: InteriorColoring,
+    /// How quickly the hue cycles as the value increases.
+    pub hue_scale: f64,
+    /// How quickly the brightness cycles as the value increases.
+    pub brightness_scale: f64,
+    /// The gamma the final colors are corrected for, where 1 leaves them
+    /// unchanged.
+    pub gamma: f64,
+    /// The point the orbit trap is centered on, as its real and imaginary
+    /// parts.
+    pub trap_center: [f64; 2],
+    /// The radius of the circle orbit trap.
+    pub trap_radius: f64,
+}
+
+/// How color brightness is compressed into the displayable range before it is
+/// quantized.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToneMap {
+    Clamp,
+    Reinhard,
+    Filmic,
+}
+
+/// How the values generated for each pixel are mapped to colors.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColoringMode {
+    /// Colors cycle with the smoothed iteration count.
+    EscapeTime,
+    /// Colors are distributed by the cumulative distribution of iteration
+    /// counts across the whole image.
+    Histogram,
+    /// Brightness follows the estimated distance to the boundary of the
+    /// fractal, giving crisp edges on thin filaments.
+    Distance,
+    /// Colors follow how close each orbit comes to a trap shape.
+    OrbitTrap(OrbitTrap),
+}
+
+/// A shape that orbits are measured against for orbit-trap coloring.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OrbitTrap {
+    /// A point at the trap center.
+    Point,
+    /// Lines parallel to the real and imaginary axes through the trap center.
+    Cross,
+    /// A circle of the trap radius around the trap center.
+    Circle,
+}
+
+/// How points that never escape are colored.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InteriorColoring {
+    /// Every interior point is the interior color.
+    Solid,
+    /// Colors follow the angle of the final value of z.
+    Angle,
+    /// Colors follow the magnitude of the final value of z relative to the
+    /// escape radius.
+    Magnitude,
+}
+
+/// The kind of fractal a ValueGenerator generates.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FractalType {
+    /// `z = z^2 + c` where `c` is the location on the plane.
+    Mandelbrot,
+    /// `z = z^2 + c` where `z` starts at the location on the plane.
+    Julia,
+    /// `z = (|re(z)| + |im(z)|i)^2 + c` where `c` is the location on the plane.
+    BurningShip,
+    /// `z = conj(z)^2 + c` where `c` is the location on the plane, also known
+    /// as the Mandelbar.
+    Tricorn,
+    /// Newton's method for the roots of `z^3 + cz - 1`, where `z` starts at
+    /// the location on the plane and points are colored by the root they
+    /// converge to. This ignores the power and coloring mode.
+    Newton,
+}
+
+/// The floating point precision z is iterated in.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Precision {
+    /// Single precision, which is faster but pixelates at deep zooms. Only
+    /// used for escape-time and histogram coloring.
+    F32,
+    /// Double precision.
+    F64,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Smoothing {
     None,
     LogarithmicDistance {
@@ -20,6 +158,177 @@ pub enum Smoothing {
     LinearIntersection,
 }
 
+impl FractalType {
+    /// Whether this fractal's `c` value is fixed rather than taken from the
+    /// location on the plane.
+    pub fn is_julia(&self) -> bool {
+        *self == FractalType::Julia || *self == FractalType::Newton
+    }
+
+    /// Whether the distance to this fractal's boundary can be estimated from
+    /// the derivative of its iteration. The absolute value and conjugate in
+    /// the Burning Ship and Tricorn iterations aren't holomorphic, so they have
+    /// no such derivative, and Newton iterations converge instead of escaping.
+    pub fn has_distance_estimate(&self) -> bool {
+        match self {
+            FractalType::Mandelbrot | FractalType::Julia => true,
+            FractalType::BurningShip | FractalType::Tricorn | FractalType::Newton => false,
+        }
+    }
+}
+
+impl FromStr for FractalType {
+    type Err = ParseFractalTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mandelbrot" => Ok(FractalType::Mandelbrot),
+            "julia" => Ok(FractalType::Julia),
+            "burning-ship" | "burningship" => Ok(FractalType::BurningShip),
+            "tricorn" | "mandelbar" => Ok(FractalType::Tricorn),
+            "newton" => Ok(FractalType::Newton),
+            _ => Err(ParseFractalTypeError::NotFractalType),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseFractalTypeError {
+    NotFractalType,
+}
+
+impl FromStr for Precision {
+    type Err = ParsePrecisionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "f32" | "single" => Ok(Precision::F32),
+            "f64" | "double" => Ok(Precision::F64),
+            _ => Err(ParsePrecisionError::NotPrecision),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParsePrecisionError {
+    NotPrecision,
+}
+
+impl ToneMap {
+    /// Maps a non-negative brightness into the range 0..=1.
+    pub fn apply(&self, value: f64) -> f64 {
+        match self {
+            ToneMap::Clamp => value.min(1f64),
+            ToneMap::Reinhard => value / (1f64 + value),
+            ToneMap::Filmic => {
+                // Narkowicz's fit of the ACES filmic curve
+                let mapped = (value * (2.51f64 * value + 0.03f64))
+                    / (value * (2.43f64 * value + 0.59f64) + 0.14f64);
+                mapped.max(0f64).min(1f64)
+            }
+        }
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = ParseToneMapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "clamp" => Ok(ToneMap::Clamp),
+            "reinhard" => Ok(ToneMap::Reinhard),
+            "filmic" => Ok(ToneMap::Filmic),
+            _ => Err(ParseToneMapError::NotToneMap),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseToneMapError {
+    NotToneMap,
+}
+
+impl FromStr for ColoringMode {
+    type Err = ParseColoringModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "escape-time" | "escapetime" => Ok(ColoringMode::EscapeTime),
+            "histogram" => Ok(ColoringMode::Histogram),
+            "distance" => Ok(ColoringMode::Distance),
+            _ => Err(ParseColoringModeError::NotColoringMode),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseColoringModeError {
+    NotColoringMode,
+}
+
+impl InteriorColoring {
+    /// Gets where in the range 0..=1 the final value of z of a point that
+    /// never escaped falls.
+    pub fn position(&self, result: &IterationResult, escape_radius: f64) -> f64 {
+        match self {
+            InteriorColoring::Solid => 0f64,
+            InteriorColoring::Angle => {
+                (result.z.arg() / (2f64 * std::f64::consts::PI)).rem_euclid(1f64)
+            }
+            InteriorColoring::Magnitude => (result.z.norm() / escape_radius).max(0f64).min(1f64),
+        }
+    }
+}
+
+impl FromStr for InteriorColoring {
+    type Err = ParseInteriorColoringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "solid" => Ok(InteriorColoring::Solid),
+            "angle" => Ok(InteriorColoring::Angle),
+            "magnitude" => Ok(InteriorColoring::Magnitude),
+            _ => Err(ParseInteriorColoringError::NotInteriorColoring),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseInteriorColoringError {
+    NotInteriorColoring,
+}
+
+impl OrbitTrap {
+    /// Gets the distance from a point to this trap, placed around a center
+    /// with a radius for the circle trap.
+    pub fn distance(&self, z: Complex<f64>, center: Complex<f64>, radius: f64) -> f64 {
+        let z = z - center;
+        match self {
+            OrbitTrap::Point => z.norm(),
+            OrbitTrap::Cross => z.re.abs().min(z.im.abs()),
+            OrbitTrap::Circle => (z.norm() - radius).abs(),
+        }
+    }
+}
+
+impl FromStr for OrbitTrap {
+    type Err = ParseOrbitTrapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "point" => Ok(OrbitTrap::Point),
+            "cross" => Ok(OrbitTrap::Cross),
+            "circle" | "ring" => Ok(OrbitTrap::Circle),
+            _ => Err(ParseOrbitTrapError::NotOrbitTrap),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseOrbitTrapError {
+    NotOrbitTrap,
+}
+
 impl Smoothing {
     pub fn from_logarithmic_distance(radius: f64, max_power: f64) -> Smoothing {
         let divisor = max_power.ln();
@@ -30,6 +339,8 @@ impl Smoothing {
         }
     }
 
+    /// The squared escape radius this smoothing was designed for. This is used
+    /// when no escape radius is specified.
     pub fn radius_squared(&self) -> f64 {
         match self {
             Smoothing::None => DEFAULT_RADIUS_SQUARED,
@@ -38,12 +349,18 @@ impl Smoothing {
         }
     }
 
-    pub fn smooth(
-        &self,
-        iterations: u32,
-        z_current: Complex<f64>,
-        z_previous: Complex<f64>,
-    ) -> f64 {
+    /// Turns the result of iterating a point into a continuous value. Points
+    /// that never escaped are left at their iteration count, as the
+    /// logarithmic smoothing isn't defined for them.
+    pub fn smooth(&self, result: &IterationResult, radius_squared: f64) -> f64 {
+        let iterations = result.iterations;
+        let z_current = result.z;
+        let z_previous = result.z_prev;
+
+        if !result.escaped {
+            return iterations as f64;
+        }
+
         match self {
             Smoothing::None => iterations as f64,
             Smoothing::LogarithmicDistance {
@@ -54,11 +371,11 @@ impl Smoothing {
                     return iterations as f64;
                 }
 
-                if z_previous.norm_sqr() > DEFAULT_RADIUS_SQUARED {
+                if z_previous.norm_sqr() > radius_squared {
                     return iterations as f64;
                 }
 
-                if z_current.norm_sqr() < DEFAULT_RADIUS_SQUARED {
+                if z_current.norm_sqr() < radius_squared {
                     return iterations as f64;
                 }
 
@@ -76,16 +393,10 @@ impl Smoothing {
                         let p = m * ax - ay;
 
                         (bx - if bx > ax {
-                            (m * p
-                                + (DEFAULT_RADIUS_SQUARED * m_squared + DEFAULT_RADIUS_SQUARED
-                                    - p * p)
-                                    .sqrt())
+                            (m * p + (radius_squared * m_squared + radius_squared - p * p).sqrt())
                                 / (m_squared + 1f64)
                         } else {
-                            (m * p
-                                - (DEFAULT_RADIUS_SQUARED * m_squared + DEFAULT_RADIUS_SQUARED
-                                    - p * p)
-                                    .sqrt())
+                            (m * p - (radius_squared * m_squared + radius_squared - p * p).sqrt())
                                 / (m_squared + 1f64)
                         }) / dx
                     } else {
@@ -94,16 +405,10 @@ impl Smoothing {
                         let p = m * ay - ax;
 
                         (by - if by > ay {
-                            (m * p
-                                + (DEFAULT_RADIUS_SQUARED * m_squared + DEFAULT_RADIUS_SQUARED
-                                    - p * p)
-                                    .sqrt())
+                            (m * p + (radius_squared * m_squared + radius_squared - p * p).sqrt())
                                 / (m_squared + 1f64)
                         } else {
-                            (m * p
-                                - (DEFAULT_RADIUS_SQUARED * m_squared + DEFAULT_RADIUS_SQUARED
-                                    - p * p)
-                                    .sqrt())
+                            (m * p - (radius_squared * m_squared + radius_squared - p * p).sqrt())
                                 / (m_squared + 1f64)
                         }) / dy
                     }
@@ -121,6 +426,8 @@ impl FromStr for Smoothing {
             Ok(Smoothing::None)
         } else if s_lowercase == "linear" || s_lowercase == "linearintersection" {
             Ok(Smoothing::LinearIntersection)
+        } else if s_lowercase == "logarithmic" || s_lowercase == "logarithmicdistance" {
+            Ok(Smoothing::from_logarithmic_distance(DEFAULT_RADIUS, 2f64))
         } else if let Some(captures) = SMOOTHING_REGEX.captures(&s_lowercase) {
             Ok(Smoothing::from_logarithmic_distance(
                 captures["radius"].parse::<f64>()?,
@@ -143,3 +450,116 @@ impl From<ParseFloatError> for ParseSmoothingError {
         ParseSmoothingError::ParseFloatError(e)
     }
 }
+
+impl Display for ParseFractalTypeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected julia, mandelbrot, burning-ship, tricorn, or newton"
+        )
+    }
+}
+
+impl Error for ParseFractalTypeError {}
+
+impl Display for ParsePrecisionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "expected f32 or f64")
+    }
+}
+
+impl Error for ParsePrecisionError {}
+
+impl Display for ParseToneMapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "expected clamp, reinhard, or filmic")
+    }
+}
+
+impl Error for ParseToneMapError {}
+
+impl Display for ParseColoringModeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "expected escape-time, histogram, or distance")
+    }
+}
+
+impl Error for ParseColoringModeError {}
+
+impl Display for ParseInteriorColoringError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "expected solid, angle, or magnitude")
+    }
+}
+
+impl Error for ParseInteriorColoringError {}
+
+impl Display for ParseOrbitTrapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "expected point, cross, circle, or ring")
+    }
+}
+
+impl Error for ParseOrbitTrapError {}
+
+impl Display for ParseSmoothingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseSmoothingError::NotSmoothing => write!(
+                f,
+                "expected none, linear, logarithmic, or logarithmic(radius, max_power)"
+            ),
+            ParseSmoothingError::ParseFloatError(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for ParseSmoothingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseSmoothingError::NotSmoothing => None,
+            ParseSmoothingError::ParseFloatError(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_holomorphic_fractals_have_distance_estimates() {
+        assert!(FractalType::Mandelbrot.has_distance_estimate());
+        assert!(FractalType::Julia.has_distance_estimate());
+        assert!(!FractalType::BurningShip.has_distance_estimate());
+        assert!(!FractalType::Tricorn.has_distance_estimate());
+        assert!(!FractalType::Newton.has_distance_estimate());
+    }
+
+    #[test]
+    fn reinhard_is_monotonic_and_bounded() {
+        let values: Vec<f64> = (0..=1000)
+            .map(|i| ToneMap::Reinhard.apply(i as f64 / 10f64))
+            .collect();
+
+        assert_eq!(values[0], 0f64);
+        for pair in values.windows(2) {
+            assert!(pair[0] < pair[1], "{} >= {}", pair[0], pair[1]);
+        }
+        assert!(values.iter().all(|&value| value < 1f64));
+        assert!(ToneMap::Reinhard.apply(1e6f64) > 0.999f64);
+    }
+
+    #[test]
+    fn reinhard_is_near_identity_for_dim_values() {
+        for &value in [0.001f64, 0.01f64, 0.05f64].iter() {
+            let mapped = ToneMap::Reinhard.apply(value);
+            assert!(
+                (mapped - value).abs() <= value * value,
+                "{} mapped to {}",
+                value,
+                mapped
+            );
+        }
+    }
+}
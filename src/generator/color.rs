@@ -0,0 +1,173 @@
+use super::RGBAColor;
+
+/// A color with some number of bits per channel that generated values can be
+/// turned into.
+pub trait Color: Copy + Send + Sync + From<RGBAColor> {
+    /// The type of each channel, which is also the type of the image buffers
+    /// colors of this kind are written into.
+    type Channel: Copy + Send + Sync;
+
+    /// Creates a color from HSBA values, which must be in the range 0..1.
+    fn from_hsb(hue: f64, saturation: f64, brightness: f64, alpha: f64) -> Self;
+
+    /// Interpolates linearly between two colors, where `t` is in the range
+    /// 0..1.
+    fn lerp(from: RGBAColor, to: RGBAColor, t: f64) -> Self;
+
+    /// Averages a number of colors together channel by channel.
+    fn average<I: Iterator<Item = Self>>(colors: I) -> Self;
+
+    /// Applies gamma correction to the red, green, and blue channels, leaving
+    /// alpha as it is.
+    fn gamma_corrected(&self, gamma: f64) -> Self;
+
+    /// Gets the red, green, blue, and alpha channels in that order.
+    fn channels(&self) -> [Self::Channel; 4];
+}
+
+/// A color with 16 bits per channel, for images where 8 bits per channel
+/// would leave visible bands in smooth gradients.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RGBA16Color {
+    pub r: u16,
+    pub g: u16,
+    pub b: u16,
+    pub a: u16,
+}
+
+/// Converts HSB values in the range 0..1 into red, green, and blue values in
+/// the range 0..1.
+pub fn hsb_to_rgb(hue: f64, saturation: f64, brightness: f64) -> [f64; 3] {
+    if saturation == 0f64 {
+        return [brightness; 3];
+    }
+
+    let sector = (hue - hue.floor()) * 6f64;
+    let offset_in_sector = sector - sector.floor();
+    let off = brightness * (1f64 - saturation);
+    let fade_out = brightness * (1f64 - saturation * offset_in_sector);
+    let fade_in = brightness * (1f64 - saturation * (1f64 - offset_in_sector));
+    match sector as u32 {
+        0 => [brightness, fade_in, off],
+        1 => [fade_out, brightness, off],
+        2 => [off, brightness, fade_in],
+        3 => [off, fade_out, brightness],
+        4 => [fade_in, off, brightness],
+        5 => [brightness, off, fade_out],
+        _ => unreachable!("Invalid color wheel sector"),
+    }
+}
+
+impl Color for RGBAColor {
+    type Channel = u8;
+
+    fn from_hsb(hue: f64, saturation: f64, brightness: f64, alpha: f64) -> Self {
+        RGBAColor::from_hsb(hue, saturation, brightness, alpha)
+    }
+
+    fn lerp(from: RGBAColor, to: RGBAColor, t: f64) -> Self {
+        let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t + 0.5f64) as u8;
+
+        RGBAColor::new(
+            lerp(from.r, to.r),
+            lerp(from.g, to.g),
+            lerp(from.b, to.b),
+            lerp(from.a, to.a),
+        )
+    }
+
+    fn average<I: Iterator<Item = Self>>(colors: I) -> Self {
+        RGBAColor::average(colors)
+    }
+
+    fn gamma_corrected(&self, gamma: f64) -> Self {
+        RGBAColor::gamma_corrected(self, gamma)
+    }
+
+    fn channels(&self) -> [u8; 4] {
+        (*self).into()
+    }
+}
+
+impl RGBA16Color {
+    /// Creates a new RGBA16Color from the given channel values.
+    pub fn new(red: u16, green: u16, blue: u16, alpha: u16) -> RGBA16Color {
+        RGBA16Color {
+            r: red,
+            g: green,
+            b: blue,
+            a: alpha,
+        }
+    }
+}
+
+impl Color for RGBA16Color {
+    type Channel = u16;
+
+    fn from_hsb(hue: f64, saturation: f64, brightness: f64, alpha: f64) -> Self {
+        let [red, green, blue] = hsb_to_rgb(hue, saturation, brightness);
+        let channel = |value: f64| (value * 65535f64 + 0.5f64) as u16;
+
+        RGBA16Color::new(channel(red), channel(green), channel(blue), channel(alpha))
+    }
+
+    fn lerp(from: RGBAColor, to: RGBAColor, t: f64) -> Self {
+        // interpolate before scaling up so the result keeps the extra precision
+        let lerp = |a: u8, b: u8| ((a as f64 + (b as f64 - a as f64) * t) * 257f64 + 0.5f64) as u16;
+
+        RGBA16Color::new(
+            lerp(from.r, to.r),
+            lerp(from.g, to.g),
+            lerp(from.b, to.b),
+            lerp(from.a, to.a),
+        )
+    }
+
+    fn average<I: Iterator<Item = Self>>(colors: I) -> Self {
+        let mut sums = [0u64; 4];
+        let mut count = 0u64;
+        for color in colors {
+            sums[0] += color.r as u64;
+            sums[1] += color.g as u64;
+            sums[2] += color.b as u64;
+            sums[3] += color.a as u64;
+            count += 1;
+        }
+
+        if count == 0 {
+            return RGBA16Color::new(0, 0, 0, 0);
+        }
+
+        let channel = |sum: u64| ((sum + count / 2) / count) as u16;
+        RGBA16Color::new(
+            channel(sums[0]),
+            channel(sums[1]),
+            channel(sums[2]),
+            channel(sums[3]),
+        )
+    }
+
+    fn gamma_corrected(&self, gamma: f64) -> Self {
+        let correct = |channel: u16| {
+            ((channel as f64 / 65535f64).powf(1f64 / gamma) * 65535f64 + 0.5f64) as u16
+        };
+
+        RGBA16Color::new(correct(self.r), correct(self.g), correct(self.b), self.a)
+    }
+
+    fn channels(&self) -> [u16; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+}
+
+impl From<RGBAColor> for RGBA16Color {
+    fn from(color: RGBAColor) -> Self {
+        // 257 maps 255 onto 65535 exactly
+        RGBA16Color::new(
+            color.r as u16 * 257,
+            color.g as u16 * 257,
+            color.b as u16 * 257,
+            color.a as u16 * 257,
+        )
+    }
+}
@@ -1,44 +1,47 @@
-use args::Smoothing;
+use args::{
+    ColoringMode, ColoringParams, FractalParams, FractalType, InteriorColoring, Precision, ToneMap,
+};
 use num_complex::Complex;
+use num_traits::Float;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::{
-    intrinsics::transmute,
+    any::Any,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    panic::{self, AssertUnwindSafe},
     sync::{
-        mpsc::{channel, Sender},
-        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{channel, RecvTimeoutError},
+        Arc,
     },
-    thread,
-    thread::JoinHandle,
-    time::{Duration, Instant},
+    time::Duration,
 };
 
+pub use color::{Color, RGBA16Color};
+
 pub mod args;
+mod color;
+pub mod palette;
 pub mod view;
 
 #[derive(Debug, Clone)]
 pub struct ValueGenerator {
     view: view::View,
-    mandelbrot: bool,
-    iterations: u32,
-    smoothing: Smoothing,
+    params: FractalParams,
+    coloring: ColoringParams,
     c: Complex<f64>,
+    /// The roots Newton fractals converge to, which move with `c`.
+    roots: [Complex<f64>; 3],
 }
 
-pub struct FractalThread {
-    name: String,
-    progress: RwLock<f32>,
-    state: RwLock<FractalThreadState>,
-    thread: Mutex<Option<JoinHandle<()>>>,
-}
-
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub enum FractalThreadState {
-    NotStarted,
-    Running,
-    Finished,
+/// A set of fractal threads that are kept alive between frames so they don't
+/// have to be respawned for every fractal generated.
+pub struct FractalThreadPool {
+    pool: rayon::ThreadPool,
 }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct RGBAColor {
     pub r: u8,
     pub g: u8,
@@ -46,72 +49,402 @@ pub struct RGBAColor {
     pub a: u8,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct FractalThreadMessage {
-    index: usize,
-    color: RGBAColor,
+/// The state of a single location after it has been iterated.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct IterationResult {
+    /// The number of iterations performed before z escaped or the iteration
+    /// limit was reached.
+    pub iterations: u32,
+    /// The final value of z.
+    pub z: Complex<f64>,
+    /// The value of z one iteration before the final value.
+    pub z_prev: Complex<f64>,
+    /// The derivative of z with respect to the location, if it was tracked.
+    pub derivative: Complex<f64>,
+    /// Whether z escaped before the iteration limit.
+    pub escaped: bool,
+    /// The closest the orbit came to the orbit trap, if one is being used.
+    pub trap_distance: f64,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub enum FractalGenerationError {}
+/// The distance in pixels over which distance coloring fades from black to
+/// full brightness.
+const DISTANCE_FALLOFF: f64 = 16f64;
+
+/// How close an orbit must come to a previous point for it to be considered
+/// periodic, squared.
+const PERIOD_TOLERANCE_SQUARED: f64 = 1e-24f64;
+
+/// The same as `PERIOD_TOLERANCE_SQUARED` for single precision iteration,
+/// which can't get anywhere near as close.
+const PERIOD_TOLERANCE_SQUARED_F32: f32 = 1e-12f32;
 
+/// How quickly orbit-trap coloring fades as orbits stay further from the trap.
+const ORBIT_TRAP_FALLOFF: f64 = 4f64;
+
+/// How close z must come to a root for Newton's method to have converged,
+/// squared.
+const NEWTON_TOLERANCE_SQUARED: f64 = 1e-12f64;
+
+/// How quickly Newton fractal basins darken as points take more iterations to
+/// converge.
+const NEWTON_FALLOFF: f64 = 0.05f64;
+
+/// The number of steps taken to find the roots Newton fractals converge to.
+const NEWTON_ROOT_STEPS: u32 = 200;
+
+/// The color used for pixels that could not be generated.
+const PARTIAL_FRAME_COLOR: RGBAColor = RGBAColor {
+    r: 255,
+    g: 0,
+    b: 255,
+    a: 255,
+};
+
+#[derive(Debug, Clone)]
+pub enum FractalGenerationError {
+    /// A fractal thread panicked before all the pixels were generated.
+    IncompleteFrame(PartialFrame),
+    /// A fractal thread panicked after the pixels were generated, while they
+    /// were being colored.
+    ThreadPanicked(String),
+    /// Generation was stopped early by the cancel flag.
+    Cancelled,
+}
+
+/// A frame where some pixels could not be generated. The missing pixels are
+/// filled with a sentinel color in the output image, which is otherwise
+/// complete.
+#[derive(Debug, Clone)]
+pub struct PartialFrame {
+    pub missing_pixels: usize,
+    /// The message of the panic that stopped generation.
+    pub panic_message: String,
+}
+
+/// Generates a fractal into an RGBA image buffer, which must hold exactly
+/// `image_width * image_height * 4` bytes. The buffer can be reused between
+/// frames to avoid reallocating it.
 pub fn generate_fractal<P: Fn(Vec<f32>)>(
     generator: &ValueGenerator,
-    num_threads: usize,
+    image: &mut [u8],
+    pool: &FractalThreadPool,
+    cancel: &Arc<AtomicBool>,
     progress_callback: P,
     progress_interval: Duration,
-) -> Result<Box<[u8]>, FractalGenerationError> {
+) -> Result<(), FractalGenerationError> {
+    generate_colored_fractal::<RGBAColor, P, _>(
+        generator,
+        image,
+        pool,
+        cancel,
+        progress_callback,
+        progress_interval,
+        |x, y, sample| generator.gen_pixel_value(x, y, sample),
+    )
+}
+
+/// Generates a fractal like `generate_fractal`, but into an image buffer with
+/// 16 bits per channel.
+pub fn generate_fractal_16<P: Fn(Vec<f32>)>(
+    generator: &ValueGenerator,
+    image: &mut [u16],
+    pool: &FractalThreadPool,
+    cancel: &Arc<AtomicBool>,
+    progress_callback: P,
+    progress_interval: Duration,
+) -> Result<(), FractalGenerationError> {
+    generate_colored_fractal::<RGBA16Color, P, _>(
+        generator,
+        image,
+        pool,
+        cancel,
+        progress_callback,
+        progress_interval,
+        |x, y, sample| generator.gen_pixel_value(x, y, sample),
+    )
+}
+
+/// Generates a fractal into an RGBA image buffer of any kind of color, getting
+/// the value of each sample of each pixel from `pixel_value`.
+fn generate_colored_fractal<C: Color, P: Fn(Vec<f32>), V: Fn(u32, u32, usize) -> f64 + Sync>(
+    generator: &ValueGenerator,
+    image: &mut [C::Channel],
+    pool: &FractalThreadPool,
+    cancel: &Arc<AtomicBool>,
+    progress_callback: P,
+    progress_interval: Duration,
+    pixel_value: V,
+) -> Result<(), FractalGenerationError> {
     let width = generator.view.image_width;
     let height = generator.view.image_height;
+    let pixels = width as usize * height as usize;
+
+    // each pixel is made up of `samples` values that are averaged together
+    let samples = generator.samples_per_pixel();
+
+    assert_eq!(
+        image.len(),
+        pixels * 4,
+        "the image buffer doesn't match the view"
+    );
 
-    let mut threads = vec![];
+    let mut values = vec![f64::INFINITY; pixels * samples];
+    let mut received = vec![false; pixels * samples];
 
-    for i in 0..num_threads {
-        threads.push(FractalThread::new(format!("Fractal Thread {}", i)));
+    let completed = AtomicUsize::new(0);
+    let (done_tx, done_rx) = channel::<()>();
+
+    // a panicking pixel stops the rest of the frame, leaving some pixels missing
+    let generation_result = panic::catch_unwind(AssertUnwindSafe(|| {
+        pool.pool.in_place_scope(|scope| {
+            scope.spawn(|_| {
+                // move the sender in so it is dropped if this panics, which
+                // stops the progress loop below
+                let done_tx = done_tx;
+
+                values
+                    .par_chunks_mut(samples)
+                    .zip(received.par_chunks_mut(samples))
+                    .enumerate()
+                    // stop taking new pixels once the cancel flag is set
+                    .try_for_each(|(index, (pixel_values, pixel_received))| {
+                        if cancel.load(Ordering::Relaxed) {
+                            return Err(());
+                        }
+
+                        let x = (index % width as usize) as u32;
+                        let y = (index / width as usize) as u32;
+
+                        for (sample, value) in pixel_values.iter_mut().enumerate() {
+                            *value = pixel_value(x, y, sample);
+                        }
+                        for received in pixel_received.iter_mut() {
+                            *received = true;
+                        }
+
+                        completed.fetch_add(1, Ordering::Relaxed);
+
+                        Ok(())
+                    })
+                    .ok();
+
+                let _ = done_tx.send(());
+            });
+
+            // this thread isn't part of the pool, so it is free to report progress
+            // every now and then until the pool finishes or fails
+            while let Err(RecvTimeoutError::Timeout) = done_rx.recv_timeout(progress_interval) {
+                progress_callback(vec![
+                    completed.load(Ordering::Relaxed) as f32 / pixels as f32,
+                ]);
+            }
+
+            // the last pixels often finish between reports, so report where
+            // generation ended up, which is all of it unless it was stopped
+            progress_callback(vec![
+                completed.load(Ordering::Relaxed) as f32 / pixels as f32,
+            ]);
+        })
+    }));
+
+    if cancel.load(Ordering::Relaxed) {
+        return Err(FractalGenerationError::Cancelled);
     }
 
-    let rx = {
-        let (tx, rx) = channel();
-        let left_over = width as usize * height as usize % num_threads;
+    // histogram coloring needs every value before it can color anything, and
+    // Newton fractals are colored by root instead
+    if generator.coloring.mode == ColoringMode::Histogram
+        && generator.params.fractal_type != FractalType::Newton
+    {
+        equalize_histogram(&mut values, generator.params.iterations);
+    }
 
-        // start all the threads
-        for (index, thread) in threads.iter().enumerate() {
-            let chunk_height = width as usize * height as usize / num_threads
-                + if index < left_over { 1 } else { 0 };
-            thread.start_generation(
-                tx.clone(),
-                width,
-                chunk_height,
-                index,
-                num_threads,
-                &generator,
-            );
+    let coloring_result = panic::catch_unwind(AssertUnwindSafe(|| {
+        pool.pool.install(|| {
+            image
+                .par_chunks_mut(4)
+                .zip(values.par_chunks(samples))
+                .zip(received.par_chunks(samples))
+                .map(|((pixel, pixel_values), pixel_received)| {
+                    let (color, missing) = if pixel_received.iter().all(|r| *r) {
+                        (
+                            C::average(
+                                pixel_values
+                                    .iter()
+                                    .map(|value| generator.gen_color::<C>(*value)),
+                            ),
+                            0,
+                        )
+                    } else {
+                        (PARTIAL_FRAME_COLOR.into(), 1)
+                    };
+                    pixel.copy_from_slice(&color.channels());
+                    missing
+                })
+                .sum::<usize>()
+        })
+    }));
+
+    let missing_pixels = coloring_result
+        .map_err(|payload| FractalGenerationError::ThreadPanicked(panic_message(payload)))?;
+
+    match generation_result {
+        Ok(()) => Ok(()),
+        Err(payload) => Err(FractalGenerationError::IncompleteFrame(PartialFrame {
+            missing_pixels,
+            panic_message: panic_message(payload),
+        })),
+    }
+}
+
+/// Finds the three roots of `z^3 + cz - 1` with the Durand-Kerner method,
+/// which finds all the roots of a polynomial at once.
+fn newton_roots(c: Complex<f64>) -> [Complex<f64>; 3] {
+    let polynomial = |z: Complex<f64>| z * z * z + c * z - 1f64;
+
+    // the starting guesses just need to be distinct and not symmetric
+    let seed = Complex::<f64>::new(0.4f64, 0.9f64);
+    let mut roots = [Complex::<f64>::new(1f64, 0f64), seed, seed * seed];
+
+    for _ in 0..NEWTON_ROOT_STEPS {
+        let previous = roots;
+        for (index, root) in roots.iter_mut().enumerate() {
+            let denominator = previous
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != index)
+                .fold(Complex::<f64>::new(1f64, 0f64), |product, (_, &other)| {
+                    product * (*root - other)
+                });
+            *root -= polynomial(*root) / denominator;
         }
+    }
+
+    roots
+}
+
+/// Gets the message a thread panicked with, if it panicked with a string.
+pub fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_owned()
+    }
+}
 
-        rx
-    };
+/// Replaces the value of every escaped pixel with its position in the
+/// cumulative distribution of escaped values, scaled back up to the iteration
+/// range. This spreads colors evenly regardless of how the iteration counts
+/// are clustered. Values that did not escape are left as they are.
+fn equalize_histogram(values: &mut [f64], iterations: u32) {
+    if iterations == 0 {
+        return;
+    }
 
-    let mut image = vec![0u8; (width * height * 4) as usize].into_boxed_slice();
+    let buckets = iterations as usize;
+    let bucket_of = |value: f64| (value.max(0f64) as usize).min(buckets - 1);
 
-    let mut previous_progress = Instant::now();
+    let mut histogram = vec![0u64; buckets];
+    let mut total = 0u64;
+    for &value in values.iter() {
+        if value < iterations as f64 {
+            histogram[bucket_of(value)] += 1;
+            total += 1;
+        }
+    }
 
-    for message in rx {
-        let FractalThreadMessage { index, color } = message;
-        image[index * 4..index * 4 + 4].copy_from_slice(&Into::<[u8; 4]>::into(color));
+    if total == 0 {
+        return;
+    }
 
-        // send progress reports every now and then
-        let now = Instant::now();
-        if now.saturating_duration_since(previous_progress) > progress_interval {
-            let mut thread_progress = vec![];
-            for thread in threads.iter() {
-                thread_progress.push(thread.get_progress());
-            }
+    // cdf[i] is the fraction of escaped values below bucket i
+    let mut cdf = Vec::with_capacity(buckets + 1);
+    let mut count = 0u64;
+    cdf.push(0f64);
+    for bucket in histogram {
+        count += bucket;
+        cdf.push(count as f64 / total as f64);
+    }
 
-            progress_callback(thread_progress);
+    for value in values.iter_mut() {
+        if *value < iterations as f64 {
+            let bucket = bucket_of(*value);
+            // interpolate within the bucket so smoothing is preserved
+            let fraction = (*value - bucket as f64).max(0f64).min(1f64);
+            let position = cdf[bucket] + (cdf[bucket + 1] - cdf[bucket]) * fraction;
 
-            previous_progress = now;
+            // keep the result below the iteration count so it is still colored
+            // as escaped
+            *value = position * (iterations - 1) as f64;
         }
     }
+}
+
+/// Generates a grayscale image of the estimated distance from each pixel to the
+/// boundary of the fractal, normalized so that the farthest pixel is white.
+/// Pixels inside the fractal are also white.
+pub fn generate_distance_image(
+    generator: &ValueGenerator,
+    pool: &FractalThreadPool,
+    cancel: &Arc<AtomicBool>,
+) -> Result<Box<[u8]>, FractalGenerationError> {
+    let width = generator.view.image_width as usize;
+    let height = generator.view.image_height as usize;
+
+    let mut distances = vec![f64::INFINITY; width * height];
+
+    let generation_result = panic::catch_unwind(AssertUnwindSafe(|| {
+        pool.pool.in_place_scope(|scope| {
+            scope.spawn(|_| {
+                distances
+                    .par_chunks_mut(width.max(1))
+                    .enumerate()
+                    // stop taking new rows once the cancel flag is set
+                    .try_for_each(|(y, row)| {
+                        if cancel.load(Ordering::Relaxed) {
+                            return Err(());
+                        }
+
+                        for (x, distance) in row.iter_mut().enumerate() {
+                            *distance = generator.gen_distance(
+                                generator.view.get_plane_coordinates((x as u32, y as u32)),
+                            );
+                        }
+
+                        Ok(())
+                    })
+                    .ok();
+            });
+        })
+    }));
+
+    if cancel.load(Ordering::Relaxed) {
+        return Err(FractalGenerationError::Cancelled);
+    }
+
+    generation_result
+        .map_err(|payload| FractalGenerationError::ThreadPanicked(panic_message(payload)))?;
+
+    // normalize against the largest finite distance
+    let max_distance = distances
+        .iter()
+        .cloned()
+        .filter(|d| d.is_finite())
+        .fold(0f64, f64::max);
+
+    let mut image = vec![0u8; width * height * 4].into_boxed_slice();
+    for (index, distance) in distances.into_iter().enumerate() {
+        let brightness = if distance.is_finite() && max_distance > 0f64 {
+            distance / max_distance
+        } else {
+            1f64
+        };
+        let value = (brightness * 255f64 + 0.5f64) as u8;
+        image[index * 4..index * 4 + 4].copy_from_slice(&[value, value, value, 255]);
+    }
 
     Ok(image)
 }
@@ -120,146 +453,517 @@ impl ValueGenerator {
     /// Creates a new ValueGenerator.
     pub fn new(
         view: view::View,
-        mandelbrot: bool,
-        iterations: u32,
-        smoothing: Smoothing,
+        params: FractalParams,
+        coloring: ColoringParams,
         c: Complex<f64>,
     ) -> ValueGenerator {
         ValueGenerator {
             view,
-            mandelbrot,
-            iterations,
-            smoothing,
+            params,
+            coloring,
             c,
+            roots: if params.fractal_type == FractalType::Newton {
+                newton_roots(c)
+            } else {
+                [Complex::<f64>::new(0f64, 0f64); 3]
+            },
         }
     }
 
-    /// Gets the value at a specific location on the fractal described by this
-    /// ValueGenerator.
-    pub fn gen_value(&self, loc: Complex<f64>) -> f64 {
-        let (mut z, c): (Complex<f64>, Complex<f64>) = if self.mandelbrot {
+    /// Gets the view of the plane this generator generates.
+    pub fn view(&self) -> &view::View {
+        &self.view
+    }
+
+    /// Gets a copy of this generator that generates the same fractal into an
+    /// image `factor` times as wide and tall.
+    pub fn upscaled(&self, factor: u32) -> ValueGenerator {
+        ValueGenerator {
+            view: self.view.upscaled(factor),
+            ..self.clone()
+        }
+    }
+
+    /// Iterates a specific location on the fractal described by this
+    /// ValueGenerator until it escapes or the iteration limit is reached. The
+    /// derivative is only tracked when `track_derivative` is set, as it
+    /// noticeably slows down iteration.
+    pub fn iterate(&self, loc: Complex<f64>, track_derivative: bool) -> IterationResult {
+        let (mut z, c, mut dz, dc): (Complex<f64>, Complex<f64>, Complex<f64>, Complex<f64>) =
+            if self.params.fractal_type.is_julia() {
+                (
+                    loc,
+                    self.c,
+                    Complex::<f64>::new(1f64, 0f64),
+                    Complex::<f64>::new(0f64, 0f64),
+                )
+            } else {
+                (
+                    Complex::<f64>::new(0f64, 0f64),
+                    loc,
+                    Complex::<f64>::new(0f64, 0f64),
+                    Complex::<f64>::new(1f64, 0f64),
+                )
+            };
+
+        let mut z_prev = z;
+
+        let trap = match self.coloring.mode {
+            ColoringMode::OrbitTrap(trap) => Some(trap),
+            _ => None,
+        };
+        let mut trap_distance = f64::INFINITY;
+        let trap_center =
+            Complex::<f64>::new(self.coloring.trap_center[0], self.coloring.trap_center[1]);
+
+        let radius_squared = self.radius_squared();
+
+        // orbit traps and interior coloring need the whole orbit, even for
+        // interior points
+        let full_orbit =
+            trap.is_some() || self.coloring.interior_coloring != InteriorColoring::Solid;
+        let period_check = self.params.period_check && !full_orbit;
+
+        if self.params.bulb_check && !full_orbit && self.in_main_bulbs(c) {
+            return IterationResult {
+                iterations: self.params.iterations,
+                z,
+                z_prev,
+                derivative: dz,
+                escaped: false,
+                trap_distance,
+            };
+        }
+
+        // Brent's cycle detection: compare z against a reference point that is
+        // moved forward at increasing intervals
+        let mut reference = z;
+        let mut reference_interval = 1;
+
+        let mut n = 0;
+        while n < self.params.iterations {
+            if z.norm_sqr() > radius_squared {
+                break;
+            }
+
+            if period_check {
+                if n > 0 && (z - reference).norm_sqr() < PERIOD_TOLERANCE_SQUARED {
+                    // the orbit is periodic so it will never escape
+                    n = self.params.iterations;
+                    break;
+                }
+
+                if n == reference_interval {
+                    reference = z;
+                    reference_interval *= 2;
+                }
+            }
+
+            z_prev = z;
+
+            if track_derivative {
+                dz = self.pow(z, self.params.power - 1) * dz * self.params.power as f64 + dc;
+            }
+            z = self.step(z, c);
+
+            if let Some(trap) = trap {
+                trap_distance =
+                    trap_distance.min(trap.distance(z, trap_center, self.coloring.trap_radius));
+            }
+
+            n += 1;
+        }
+
+        IterationResult {
+            iterations: n,
+            z,
+            z_prev,
+            derivative: dz,
+            escaped: z.norm_sqr() > radius_squared,
+            trap_distance,
+        }
+    }
+
+    /// Gets the values z takes while iterating a specific location, starting
+    /// with its initial value and stopping once it escapes or the iteration
+    /// limit is reached.
+    pub fn orbit(&self, loc: Complex<f64>) -> Vec<Complex<f64>> {
+        let (mut z, c) = if self.params.fractal_type.is_julia() {
+            (loc, self.c)
+        } else {
             (Complex::<f64>::new(0f64, 0f64), loc)
+        };
+
+        let radius_squared = self.radius_squared();
+        let mut orbit = vec![z];
+        for _ in 0..self.params.iterations {
+            if z.norm_sqr() > radius_squared {
+                break;
+            }
+
+            z = self.step(z, c);
+            orbit.push(z);
+        }
+
+        orbit
+    }
+
+    /// Iterates a specific location like `iterate`, but in single precision,
+    /// which is faster but pixelates at deep zooms. Neither the derivative nor
+    /// orbit traps are tracked.
+    fn iterate_f32(&self, loc: Complex<f64>) -> IterationResult {
+        let to_f32 = |c: Complex<f64>| Complex::<f32>::new(c.re as f32, c.im as f32);
+        let to_f64 = |c: Complex<f32>| Complex::<f64>::new(c.re as f64, c.im as f64);
+
+        let (mut z, c) = if self.params.fractal_type.is_julia() {
+            (to_f32(loc), to_f32(self.c))
         } else {
-            (loc, self.c)
+            (Complex::<f32>::new(0f32, 0f32), to_f32(loc))
         };
 
         let mut z_prev = z;
 
-        let radius_squared = self.smoothing.radius_squared();
+        // huge escape radii would overflow to infinity, which nothing exceeds
+        let radius_squared = (self.radius_squared() as f32).min(f32::MAX);
+
+        let full_orbit = self.coloring.interior_coloring != InteriorColoring::Solid;
+        let period_check = self.params.period_check && !full_orbit;
+
+        if self.params.bulb_check && !full_orbit && self.in_main_bulbs(loc) {
+            return IterationResult {
+                iterations: self.params.iterations,
+                z: to_f64(z),
+                z_prev: to_f64(z_prev),
+                derivative: Complex::<f64>::new(0f64, 0f64),
+                escaped: false,
+                trap_distance: f64::INFINITY,
+            };
+        }
+
+        let mut reference = z;
+        let mut reference_interval = 1;
 
         let mut n = 0;
-        while n < self.iterations {
+        while n < self.params.iterations {
             if z.norm_sqr() > radius_squared {
                 break;
             }
 
-            z_prev = z;
+            if period_check {
+                if n > 0 && (z - reference).norm_sqr() < PERIOD_TOLERANCE_SQUARED_F32 {
+                    n = self.params.iterations;
+                    break;
+                }
 
-            z = z * z + c;
+                if n == reference_interval {
+                    reference = z;
+                    reference_interval *= 2;
+                }
+            }
+
+            z_prev = z;
+            z = self.step(z, c);
 
             n += 1;
         }
 
-        self.smoothing.smooth(n, z, z_prev)
+        IterationResult {
+            iterations: n,
+            z: to_f64(z),
+            z_prev: to_f64(z_prev),
+            derivative: Complex::<f64>::new(0f64, 0f64),
+            escaped: z.norm_sqr() > radius_squared,
+            trap_distance: f64::INFINITY,
+        }
     }
 
-    pub fn gen_pixel_value(&self, x: u32, y: u32) -> f64 {
-        self.gen_value(self.view.get_plane_coordinates((x, y)))
+    /// Checks whether `c` is inside the main cardioid or the period-2 bulb of
+    /// the Mandelbrot set, which never escape.
+    fn in_main_bulbs(&self, c: Complex<f64>) -> bool {
+        if self.params.fractal_type != FractalType::Mandelbrot || self.params.power != 2 {
+            return false;
+        }
+
+        let x = c.re - 0.25f64;
+        let y_squared = c.im * c.im;
+        let q = x * x + y_squared;
+        if q * (q + x) <= 0.25f64 * y_squared {
+            return true;
+        }
+
+        let x = c.re + 1f64;
+        x * x + y_squared <= 0.0625f64
     }
 
-    pub fn gen_color(&self, value: f64) -> RGBAColor {
-        if value < self.iterations as f64 {
-            RGBAColor::from_hsb(
-                mod2(value * 3.3f64, 0f64, 256f64) / 256f64,
-                1f64,
-                mod2(value * 16f64, 0f64, 256f64) / 256f64,
-                1f64,
-            )
+    /// Gets the value at a specific location on the fractal described by this
+    /// ValueGenerator. With distance coloring this is the estimated distance
+    /// to the boundary in pixels, with orbit-trap coloring it is the closest
+    /// the orbit came to the trap, and otherwise it is the smoothed iteration
+    /// count.
+    pub fn gen_value(&self, loc: Complex<f64>) -> f64 {
+        if self.params.fractal_type == FractalType::Newton {
+            return self.gen_newton_value(loc);
+        }
+
+        match self.coloring.mode {
+            ColoringMode::Distance => self.iterate(loc, true).distance() / self.view.image_scale_x,
+            ColoringMode::OrbitTrap(_) => self.iterate(loc, false).trap_distance,
+            _ => {
+                let result = match self.params.precision {
+                    Precision::F32 => self.iterate_f32(loc),
+                    Precision::F64 => self.iterate(loc, false),
+                };
+
+                // interior positions are kept above the iteration count so
+                // they are still colored as interior
+                if !result.escaped && self.coloring.interior_coloring != InteriorColoring::Solid {
+                    return self.params.iterations as f64
+                        + self
+                            .coloring
+                            .interior_coloring
+                            .position(&result, self.params.escape_radius);
+                }
+
+                self.params.smoothing.smooth(&result, self.radius_squared())
+            }
+        }
+    }
+
+    /// Runs Newton's method from a location until it converges to one of the
+    /// roots. The value is the index of the root times the iteration limit
+    /// plus the number of iterations it took, or infinity if it never
+    /// converged.
+    fn gen_newton_value(&self, loc: Complex<f64>) -> f64 {
+        let mut z = loc;
+
+        for n in 0..self.params.iterations {
+            for (index, root) in self.roots.iter().enumerate() {
+                if (z - root).norm_sqr() < NEWTON_TOLERANCE_SQUARED {
+                    return index as f64 * self.params.iterations as f64 + n as f64;
+                }
+            }
+
+            z = self.step(z, self.c);
+        }
+
+        f64::INFINITY
+    }
+
+    /// Estimates the distance from a location to the boundary of the fractal
+    /// described by this ValueGenerator. Locations that never escape are
+    /// considered infinitely far away.
+    pub fn gen_distance(&self, loc: Complex<f64>) -> f64 {
+        self.iterate(loc, true).distance()
+    }
+
+    fn radius_squared(&self) -> f64 {
+        self.params.escape_radius * self.params.escape_radius
+    }
+
+    /// Performs a single iteration of this ValueGenerator's fractal formula.
+    fn step<T: Float>(&self, z: Complex<T>, c: Complex<T>) -> Complex<T> {
+        match self.params.fractal_type {
+            FractalType::Mandelbrot | FractalType::Julia => self.pow(z, self.params.power) + c,
+            FractalType::BurningShip => {
+                let folded = Complex::<T>::new(z.re.abs(), z.im.abs());
+                self.pow(folded, self.params.power) + c
+            }
+            FractalType::Tricorn => self.pow(z.conj(), self.params.power) + c,
+            FractalType::Newton => {
+                let one = T::one();
+                let three = one + one + one;
+                let z_squared = z * z;
+                z - (z_squared * z + c * z - one) / (z_squared * three + c)
+            }
+        }
+    }
+
+    /// Raises z to an integer power, keeping the common square case fast.
+    fn pow<T: Float>(&self, z: Complex<T>, power: u32) -> Complex<T> {
+        match power {
+            1 => z,
+            2 => z * z,
+            _ => z.powu(power),
+        }
+    }
+
+    /// The number of values generated for each pixel when supersampling.
+    pub fn samples_per_pixel(&self) -> usize {
+        self.params.supersample as usize * self.params.supersample as usize
+    }
+
+    /// Gets the value of one of the supersampled points within a pixel.
+    /// Samples are spread evenly in a grid around the pixel's location.
+    pub fn gen_pixel_value(&self, x: u32, y: u32, sample: usize) -> f64 {
+        let supersample = self.params.supersample as usize;
+        let offset = |sample: usize| (sample as f64 + 0.5f64) / supersample as f64 - 0.5f64;
+
+        self.gen_value(self.view.get_plane_coordinates_offset(
+            (x, y),
+            (offset(sample % supersample), offset(sample / supersample)),
+        ))
+    }
+
+    pub fn gen_color<C: Color>(&self, value: f64) -> C {
+        let color: C = self.gen_uncorrected_color(value);
+
+        if self.coloring.gamma == 1f64 {
+            color
         } else {
-            RGBAColor::new(0, 0, 0, 255)
+            color.gamma_corrected(self.coloring.gamma)
         }
     }
 
-    pub fn gen_pixel(&self, x: u32, y: u32) -> RGBAColor {
-        self.gen_color(self.gen_pixel_value(x, y))
+    /// Colors a value before gamma correction is applied.
+    fn gen_uncorrected_color<C: Color>(&self, value: f64) -> C {
+        if self.params.fractal_type == FractalType::Newton {
+            return self.gen_newton_color(value);
+        }
+
+        match self.coloring.mode {
+            ColoringMode::Distance => return self.gen_distance_color(value),
+            ColoringMode::OrbitTrap(_) => return self.gen_trap_color(value),
+            _ => {}
+        }
+
+        if value >= self.params.iterations as f64 || value.is_nan() {
+            return self.gen_interior_color(value);
+        }
+
+        if let Some(period) = self.coloring.palette_period {
+            let position = mod2(value, 0f64, period) / period;
+            return match &self.coloring.palette {
+                Some(palette) => palette.color_at(position),
+                None => C::from_hsb(position, 1f64, self.escape_brightness(value), 1f64),
+            };
+        }
+
+        if let Some(palette) = &self.coloring.palette {
+            return palette.color_at(value / self.params.iterations as f64);
+        }
+
+        C::from_hsb(
+            mod2(value * self.coloring.hue_scale, 0f64, 256f64) / 256f64,
+            1f64,
+            self.escape_brightness(value),
+            1f64,
+        )
     }
-}
 
-impl FractalThread {
-    pub fn new(name: String) -> Arc<FractalThread> {
-        Arc::new(FractalThread {
-            name,
-            progress: RwLock::new(0f32),
-            state: RwLock::new(FractalThreadState::NotStarted),
-            thread: Mutex::new(None),
-        })
+    /// Gets the brightness of a point that escaped. Without a tone map the
+    /// brightness cycles as the value grows, otherwise the unbounded
+    /// brightness is compressed so bright values approach full brightness
+    /// instead of wrapping back around to black.
+    fn escape_brightness(&self, value: f64) -> f64 {
+        match self.coloring.tone_map {
+            ToneMap::Clamp => mod2(value * self.coloring.brightness_scale, 0f64, 256f64) / 256f64,
+            tone_map => tone_map.apply((value * self.coloring.brightness_scale / 256f64).max(0f64)),
+        }
     }
 
-    pub fn start_generation(
-        self: &Arc<Self>,
-        img_data: Sender<FractalThreadMessage>,
-        chunk_width: u32,
-        size: usize,
-        offset: usize,
-        skip: usize,
-        generator: &ValueGenerator,
-    ) {
-        let mut state = self.state.write().unwrap();
-        if *state != FractalThreadState::Running {
-            *state = FractalThreadState::Running;
-            *self.progress.write().unwrap() = 0f32;
-            let clone = self.clone();
-            let generator = generator.clone();
-            *self.thread.lock().unwrap() = Some(
-                thread::Builder::new()
-                    .name(self.name.clone())
-                    .spawn(move || {
-                        clone.image_thread_func(
-                            img_data,
-                            chunk_width,
-                            size,
-                            offset,
-                            skip,
-                            generator,
-                        )
-                    })
-                    .expect("Unable to spawn fractal thread"),
-            );
+    /// Colors a point that never escaped, whose value is the iteration count
+    /// plus its position from the interior coloring.
+    fn gen_interior_color<C: Color>(&self, value: f64) -> C {
+        if self.coloring.interior_coloring == InteriorColoring::Solid {
+            return self.coloring.interior_color.into();
+        }
+
+        let position = (value - self.params.iterations as f64).max(0f64).min(1f64);
+        match &self.coloring.palette {
+            Some(palette) => palette.color_at(position),
+            None => C::from_hsb(position, 1f64, 1f64, 1f64),
         }
     }
 
-    fn image_thread_func(
-        &self,
-        img_data: Sender<FractalThreadMessage>,
-        chunk_width: u32,
-        size: usize,
-        offset: usize,
-        skip: usize,
-        generator: ValueGenerator,
-    ) {
-        for i in 0usize..size {
-            let index = i * skip + offset;
+    /// Colors a Newton fractal value with a hue for the root it converged to,
+    /// darkening the longer it took to converge.
+    fn gen_newton_color<C: Color>(&self, value: f64) -> C {
+        if !value.is_finite() {
+            return self.coloring.interior_color.into();
+        }
+
+        let iterations = self.params.iterations as f64;
+        let root = (value / iterations).floor();
+        let brightness = self
+            .coloring
+            .tone_map
+            .apply((-(value - root * iterations) * NEWTON_FALLOFF).exp());
+
+        let position = root / self.roots.len() as f64;
+        let base = match &self.coloring.palette {
+            Some(palette) => palette.color_at(position),
+            None => RGBAColor::from_hsb(position, 1f64, 1f64, 1f64),
+        };
+
+        C::lerp(RGBAColor::new(0, 0, 0, base.a), base, brightness)
+    }
 
-            let x = (index % chunk_width as usize) as u32;
-            let y = (index / chunk_width as usize) as u32;
+    /// Colors a distance to the boundary in pixels, fading from black at the
+    /// boundary to full brightness further away.
+    fn gen_distance_color<C: Color>(&self, distance: f64) -> C {
+        if !distance.is_finite() {
+            return self.coloring.interior_color.into();
+        }
 
-            let color = generator.gen_pixel(x, y);
-            img_data
-                .send(FractalThreadMessage { index, color })
-                .unwrap();
+        let brightness = self
+            .coloring
+            .tone_map
+            .apply((distance / DISTANCE_FALLOFF).max(0f64).sqrt());
 
-            *self.progress.write().unwrap() = (i + 1) as f32 / size as f32;
+        match &self.coloring.palette {
+            Some(palette) => palette.color_at(brightness),
+            None => C::from_hsb(0f64, 0f64, brightness, 1f64),
         }
+    }
+
+    /// Colors the closest distance an orbit came to the orbit trap, with
+    /// orbits that pass closer to the trap being brighter.
+    fn gen_trap_color<C: Color>(&self, trap_distance: f64) -> C {
+        let closeness = 1f64 / (1f64 + trap_distance.max(0f64) * ORBIT_TRAP_FALLOFF);
 
-        *self.state.write().unwrap() = FractalThreadState::Finished;
+        match &self.coloring.palette {
+            Some(palette) => palette.color_at(closeness),
+            None => C::from_hsb(
+                mod2(closeness * self.coloring.hue_scale, 0f64, 1f64),
+                1f64,
+                self.coloring.tone_map.apply(closeness),
+                1f64,
+            ),
+        }
     }
+}
 
-    pub fn get_progress(&self) -> f32 {
-        *self.progress.read().unwrap()
+impl IterationResult {
+    /// Estimates the distance to the boundary of the fractal from the final
+    /// z and its derivative. Results that never escaped are considered
+    /// infinitely far away.
+    pub fn distance(&self) -> f64 {
+        if !self.escaped {
+            return f64::INFINITY;
+        }
+
+        let z_norm = self.z.norm();
+        z_norm * z_norm.ln() / self.derivative.norm()
     }
+}
 
-    pub fn get_state(&self) -> FractalThreadState {
-        *self.state.read().unwrap()
+impl FractalThreadPool {
+    /// Spawns a new pool with the given number of fractal threads, which must
+    /// be at least one.
+    pub fn new(num_threads: usize) -> FractalThreadPool {
+        // rayon would quietly pick its own number of threads for 0
+        assert!(
+            num_threads > 0,
+            "a fractal thread pool needs at least one thread"
+        );
+
+        FractalThreadPool {
+            pool: rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .thread_name(|i| format!("Fractal Thread {}", i))
+                .build()
+                .expect("Unable to spawn fractal threads"),
+        }
     }
 }
 
@@ -274,82 +978,373 @@ impl RGBAColor {
         }
     }
 
+    /// Averages a number of colors together channel by channel.
+    pub fn average<I: Iterator<Item = RGBAColor>>(colors: I) -> RGBAColor {
+        let mut sums = [0u32; 4];
+        let mut count = 0u32;
+        for color in colors {
+            sums[0] += color.r as u32;
+            sums[1] += color.g as u32;
+            sums[2] += color.b as u32;
+            sums[3] += color.a as u32;
+            count += 1;
+        }
+
+        if count == 0 {
+            return RGBAColor::new(0, 0, 0, 0);
+        }
+
+        let channel = |sum: u32| ((sum + count / 2) / count) as u8;
+        RGBAColor::new(
+            channel(sums[0]),
+            channel(sums[1]),
+            channel(sums[2]),
+            channel(sums[3]),
+        )
+    }
+
+    /// Applies gamma correction to the red, green, and blue channels, leaving
+    /// alpha as it is.
+    pub fn gamma_corrected(&self, gamma: f64) -> RGBAColor {
+        let correct =
+            |channel: u8| ((channel as f64 / 255f64).powf(1f64 / gamma) * 255f64 + 0.5f64) as u8;
+
+        RGBAColor::new(correct(self.r), correct(self.g), correct(self.b), self.a)
+    }
+
     /// Creates a new RGBAColor from these HSBA values. All HSBA values must be
     /// in the range 0..1.
     pub fn from_hsb(hue: f64, saturation: f64, brightness: f64, alpha: f64) -> RGBAColor {
-        let alpha = (alpha * 255f64 + 0.5f64) as u8;
-        if saturation == 0f64 {
-            let brightness = (brightness * 255f64 + 0.5f64) as u8;
-            RGBAColor {
-                r: brightness,
-                g: brightness,
-                b: brightness,
-                a: alpha,
-            }
-        } else {
-            let sector = (hue - hue.floor()) * 6f64;
-            let offset_in_sector = sector - sector.floor();
-            let off = brightness * (1f64 - saturation);
-            let fade_out = brightness * (1f64 - saturation * offset_in_sector);
-            let fade_in = brightness * (1f64 - saturation * (1f64 - offset_in_sector));
-            match sector as u32 {
-                0 => RGBAColor {
-                    r: (brightness * 255f64 + 0.5f64) as u8,
-                    g: (fade_in * 255f64 + 0.5f64) as u8,
-                    b: (off * 255f64 + 0.5f64) as u8,
-                    a: alpha,
-                },
-                1 => RGBAColor {
-                    r: (fade_out * 255f64 + 0.5f64) as u8,
-                    g: (brightness * 255f64 + 0.5f64) as u8,
-                    b: (off * 255f64 + 0.5f64) as u8,
-                    a: alpha,
-                },
-                2 => RGBAColor {
-                    r: (off * 255f64 + 0.5f64) as u8,
-                    g: (brightness * 255f64 + 0.5f64) as u8,
-                    b: (fade_in * 255f64 + 0.5f64) as u8,
-                    a: alpha,
-                },
-                3 => RGBAColor {
-                    r: (off * 255f64 + 0.5f64) as u8,
-                    g: (fade_out * 255f64 + 0.5f64) as u8,
-                    b: (brightness * 255f64 + 0.5f64) as u8,
-                    a: alpha,
-                },
-                4 => RGBAColor {
-                    r: (fade_in * 255f64 + 0.5f64) as u8,
-                    g: (off * 255f64 + 0.5f64) as u8,
-                    b: (brightness * 255f64 + 0.5f64) as u8,
-                    a: alpha,
-                },
-                5 => RGBAColor {
-                    r: (brightness * 255f64 + 0.5f64) as u8,
-                    g: (off * 255f64 + 0.5f64) as u8,
-                    b: (fade_out * 255f64 + 0.5f64) as u8,
-                    a: alpha,
-                },
-                _ => unreachable!("Invalid color wheel sector"),
+        let [red, green, blue] = color::hsb_to_rgb(hue, saturation, brightness);
+        let channel = |value: f64| (value * 255f64 + 0.5f64) as u8;
+
+        RGBAColor::new(channel(red), channel(green), channel(blue), channel(alpha))
+    }
+}
+
+impl Display for FractalGenerationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FractalGenerationError::IncompleteFrame(frame) => write!(
+                f,
+                "a fractal thread panicked leaving {} pixels ungenerated: {}",
+                frame.missing_pixels, frame.panic_message
+            ),
+            FractalGenerationError::ThreadPanicked(message) => {
+                write!(f, "a fractal thread panicked: {}", message)
             }
+            FractalGenerationError::Cancelled => write!(f, "generation was cancelled"),
         }
     }
 }
 
-impl Into<[u8; 4]> for RGBAColor {
-    fn into(self) -> [u8; 4] {
-        unsafe { transmute(self) }
+impl Error for FractalGenerationError {}
+
+impl From<RGBAColor> for [u8; 4] {
+    fn from(color: RGBAColor) -> Self {
+        [color.r, color.g, color.b, color.a]
     }
 }
 
-fn mod2(mut value: f64, min: f64, max: f64) -> f64 {
+impl From<[u8; 4]> for RGBAColor {
+    fn from([r, g, b, a]: [u8; 4]) -> Self {
+        RGBAColor { r, g, b, a }
+    }
+}
+
+/// Wraps a value into the range `[min, max)`. Non-finite values wrap to `min`.
+fn mod2(value: f64, min: f64, max: f64) -> f64 {
+    if !value.is_finite() {
+        return min;
+    }
+
     let size = max - min;
+    let offset = (value - min).rem_euclid(size);
 
-    while value < min {
-        value += size;
+    // tiny negative offsets can round up to exactly the size
+    if offset < size {
+        min + offset
+    } else {
+        min
     }
-    while value >= max {
-        value -= size;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use args::Smoothing;
+
+    fn test_params(fractal_type: FractalType) -> FractalParams {
+        FractalParams {
+            fractal_type,
+            power: 2,
+            iterations: 100,
+            smoothing: Smoothing::None,
+            escape_radius: 100f64,
+            supersample: 1,
+            period_check: true,
+            bulb_check: true,
+            precision: Precision::F64,
+        }
+    }
+
+    fn test_coloring() -> ColoringParams {
+        ColoringParams {
+            mode: ColoringMode::EscapeTime,
+            tone_map: ToneMap::Clamp,
+            palette: None,
+            palette_period: None,
+            interior_color: RGBAColor::new(0, 0, 0, 255),
+            interior_coloring: InteriorColoring::Solid,
+            hue_scale: 3.3f64,
+            brightness_scale: 16f64,
+            gamma: 1f64,
+            trap_center: [0f64, 0f64],
+            trap_radius: 1f64,
+        }
+    }
+
+    fn mandelbrot(view: view::View) -> ValueGenerator {
+        ValueGenerator::new(
+            view,
+            test_params(FractalType::Mandelbrot),
+            test_coloring(),
+            Complex::<f64>::new(0f64, 0f64),
+        )
+    }
+
+    fn no_cancel() -> Arc<AtomicBool> {
+        Arc::new(AtomicBool::new(false))
     }
 
-    value
+    /// A single row of pixels along the real axis from -0.5 to 3.5 in steps
+    /// of 0.5.
+    fn real_axis_view() -> view::View {
+        view::View {
+            image_width: 9,
+            image_height: 1,
+            image_scale_x: 0.5f64,
+            image_scale_y: 0.5f64,
+            plane_start_x: -0.5f64,
+            plane_start_y: 0f64,
+            rotation: 0f64,
+        }
+    }
+
+    #[test]
+    fn distance_image_follows_distance_to_boundary() {
+        let generator = mandelbrot(real_axis_view());
+        let image =
+            generate_distance_image(&generator, &FractalThreadPool::new(2), &no_cancel()).unwrap();
+        let brightness = |x: usize| image[x * 4];
+
+        // -0.5 and 0 are inside the set
+        assert_eq!(brightness(0), 255);
+        assert_eq!(brightness(1), 255);
+
+        // 0.5 is just past the cusp at 0.25, while 3.5 is far outside
+        assert!(brightness(2) < 128, "near: {}", brightness(2));
+        assert!(brightness(2) < brightness(5));
+        assert!(brightness(5) < brightness(8));
+        assert_eq!(brightness(8), 255);
+    }
+
+    #[test]
+    fn distance_estimate_grows_away_from_boundary() {
+        let generator = mandelbrot(real_axis_view());
+        let distance = |re: f64| generator.gen_distance(Complex::<f64>::new(re, 0f64));
+
+        assert!(distance(0f64).is_infinite());
+        assert!(distance(0.3f64) < 0.2f64, "near: {}", distance(0.3f64));
+        assert!(distance(0.3f64) < distance(1f64));
+        assert!(distance(3.5f64) > 1f64, "far: {}", distance(3.5f64));
+    }
+
+    #[test]
+    fn panicking_thread_leaves_partial_frame() {
+        let generator = mandelbrot(view::View::new_uniform(8, 8, 4f64));
+        let mut image = vec![0u8; 8 * 8 * 4];
+
+        let result = generate_colored_fractal::<RGBAColor, _, _>(
+            &generator,
+            &mut image,
+            &FractalThreadPool::new(2),
+            &no_cancel(),
+            |_| {},
+            Duration::from_millis(10),
+            |x, y, sample| {
+                if y == 3 {
+                    panic!("row 3 failed");
+                }
+                generator.gen_pixel_value(x, y, sample)
+            },
+        );
+
+        let error = result.unwrap_err();
+        assert!(error.to_string().contains("row 3 failed"), "{}", error);
+        let partial = match error {
+            FractalGenerationError::IncompleteFrame(partial) => partial,
+            error => panic!("expected an incomplete frame, got {:?}", error),
+        };
+        assert_eq!(partial.panic_message, "row 3 failed");
+
+        let magenta: [u8; 4] = PARTIAL_FRAME_COLOR.into();
+        let is_magenta = |pixel: &[u8]| pixel == &magenta[..];
+        let row = |y: usize| &image[y * 8 * 4..(y + 1) * 8 * 4];
+
+        // the panicking row is never generated, and nothing else is lost silently
+        assert!(row(3).chunks(4).all(is_magenta));
+        assert!(partial.missing_pixels >= 8);
+        assert_eq!(
+            image.chunks(4).filter(|pixel| is_magenta(*pixel)).count(),
+            partial.missing_pixels
+        );
+    }
+
+    #[test]
+    fn fractal_does_not_depend_on_pool_size() {
+        let generator = mandelbrot(view::View::new_uniform(24, 16, 3f64));
+        let generate = |num_threads: usize| {
+            let mut image = vec![0u8; 24 * 16 * 4];
+            generate_fractal(
+                &generator,
+                &mut image,
+                &FractalThreadPool::new(num_threads),
+                &no_cancel(),
+                |_| {},
+                Duration::from_millis(10),
+            )
+            .unwrap();
+            image
+        };
+
+        let expected = generate(1);
+        for &num_threads in &[2, 4, 8] {
+            assert!(generate(num_threads) == expected, "{} threads", num_threads);
+        }
+    }
+
+    #[test]
+    fn distance_image_does_not_depend_on_pool_size() {
+        let generator = mandelbrot(view::View::new_uniform(24, 16, 3f64));
+        let generate = |num_threads: usize| {
+            generate_distance_image(
+                &generator,
+                &FractalThreadPool::new(num_threads),
+                &no_cancel(),
+            )
+            .unwrap()
+        };
+
+        let expected = generate(1);
+        for &num_threads in &[2, 4, 8] {
+            assert!(generate(num_threads) == expected, "{} threads", num_threads);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one thread")]
+    fn empty_pool_is_rejected() {
+        FractalThreadPool::new(0);
+    }
+
+    #[test]
+    fn tiny_images_generate_with_many_threads() {
+        let generator = mandelbrot(view::View::new_uniform(2, 2, 3f64));
+        let pool = FractalThreadPool::new(16);
+
+        let mut image = vec![0u8; 2 * 2 * 4];
+        generate_fractal(
+            &generator,
+            &mut image,
+            &pool,
+            &no_cancel(),
+            |_| {},
+            Duration::from_millis(10),
+        )
+        .unwrap();
+        assert!(image.chunks(4).all(|pixel| pixel[3] == 255));
+
+        let distance_image = generate_distance_image(&generator, &pool, &no_cancel()).unwrap();
+        assert_eq!(distance_image.len(), 2 * 2 * 4);
+    }
+
+    #[test]
+    fn orbits_escape_at_the_escape_radius() {
+        for &escape_radius in &[2f64, 10f64, 1e3f64, 1e6f64] {
+            let generator = ValueGenerator::new(
+                real_axis_view(),
+                FractalParams {
+                    escape_radius,
+                    ..test_params(FractalType::Mandelbrot)
+                },
+                test_coloring(),
+                Complex::<f64>::new(0f64, 0f64),
+            );
+
+            for &c in &[0.5f64, 1f64, -2.5f64] {
+                let result = generator.iterate(Complex::<f64>::new(c, 0.25f64), false);
+                assert!(result.escaped, "{} did not escape", c);
+                assert!(result.z.norm() > escape_radius, "{:?}", result);
+                assert!(result.z_prev.norm() <= escape_radius, "{:?}", result);
+            }
+        }
+    }
+
+    #[test]
+    fn escape_brightness_cycles_without_a_tone_map() {
+        let generator = mandelbrot(real_axis_view());
+
+        assert_eq!(generator.escape_brightness(4f64), 0.25f64);
+        assert_eq!(generator.escape_brightness(20f64), 0.25f64);
+    }
+
+    #[test]
+    fn tone_mapped_escape_brightness_compresses_highlights() {
+        let generator = ValueGenerator {
+            coloring: ColoringParams {
+                tone_map: ToneMap::Reinhard,
+                ..test_coloring()
+            },
+            ..mandelbrot(real_axis_view())
+        };
+
+        // dim values are nearly unchanged
+        let dim = generator.escape_brightness(0.1f64);
+        assert!((dim - 0.1f64 * 16f64 / 256f64).abs() < 1e-4, "{}", dim);
+
+        // bright values keep getting brighter without reaching full brightness
+        let mut previous = dim;
+        for &value in &[1f64, 10f64, 20f64, 50f64, 99f64] {
+            let brightness = generator.escape_brightness(value);
+            assert!(brightness > previous && brightness < 1f64, "{}", brightness);
+            previous = brightness;
+        }
+    }
+
+    #[test]
+    fn mod2_wraps_into_range() {
+        assert_eq!(mod2(f64::NAN, 0f64, 360f64), 0f64);
+        assert_eq!(mod2(f64::INFINITY, 0f64, 360f64), 0f64);
+        assert_eq!(mod2(f64::NEG_INFINITY, -1f64, 1f64), -1f64);
+
+        assert_eq!(mod2(370f64, 0f64, 360f64), 10f64);
+        assert_eq!(mod2(-10f64, 0f64, 360f64), 350f64);
+
+        for &value in &[1e300, -1e300, -1e-300] {
+            let wrapped = mod2(value, 0f64, 360f64);
+            assert!(wrapped >= 0f64 && wrapped < 360f64, "{}", wrapped);
+        }
+    }
+
+    #[test]
+    fn cancelled_distance_image_stops() {
+        let generator = mandelbrot(real_axis_view());
+        let cancel = Arc::new(AtomicBool::new(true));
+
+        assert!(matches!(
+            generate_distance_image(&generator, &FractalThreadPool::new(2), &cancel),
+            Err(FractalGenerationError::Cancelled)
+        ));
+    }
 }
@@ -0,0 +1,180 @@
+use super::{Color, RGBAColor};
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    num::{ParseFloatError, ParseIntError},
+    str::FromStr,
+};
+
+/// A gradient of colors that fractal values are mapped onto.
+///
+/// Palettes are written as text with one stop per line in the form
+/// `position red green blue`, where `position` is in the range 0..1 and the
+/// color components are in the range 0..255. A line of the form
+/// `interior red green blue` sets the color of points inside the fractal.
+/// Anything after a `#` is a comment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedPalette")]
+pub struct Palette {
+    stops: Vec<PaletteStop>,
+    interior: RGBAColor,
+}
+
+/// A deserialized palette whose stops haven't been checked yet.
+#[derive(Deserialize)]
+struct UncheckedPalette {
+    stops: Vec<PaletteStop>,
+    interior: RGBAColor,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+struct PaletteStop {
+    position: f64,
+    color: RGBAColor,
+}
+
+impl Palette {
+    /// Gets the color at a position along the palette, interpolating linearly
+    /// between the surrounding stops.
+    pub fn color_at<C: Color>(&self, position: f64) -> C {
+        let first = &self.stops[0];
+        let last = &self.stops[self.stops.len() - 1];
+
+        if position.is_nan() || position <= first.position {
+            return first.color.into();
+        }
+        if position >= last.position {
+            return last.color.into();
+        }
+
+        let next_index = self
+            .stops
+            .iter()
+            .position(|stop| stop.position > position)
+            .unwrap();
+        let previous = &self.stops[next_index - 1];
+        let next = &self.stops[next_index];
+
+        let t = (position - previous.position) / (next.position - previous.position);
+        C::lerp(previous.color, next.color, t)
+    }
+
+    /// Gets the color of points inside the fractal.
+    pub fn interior_color(&self) -> RGBAColor {
+        self.interior
+    }
+}
+
+impl FromStr for Palette {
+    type Err = ParsePaletteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut stops = vec![];
+        let mut interior = RGBAColor::new(0, 0, 0, 255);
+
+        for (index, line) in s.lines().enumerate() {
+            let line_num = index + 1;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() != 4 {
+                return Err(ParsePaletteError::InvalidLine(line_num));
+            }
+
+            let parse_component = |s: &str| {
+                s.parse::<u8>()
+                    .map_err(|e| ParsePaletteError::ParseIntError(line_num, e))
+            };
+            let color = RGBAColor::new(
+                parse_component(parts[1])?,
+                parse_component(parts[2])?,
+                parse_component(parts[3])?,
+                255,
+            );
+
+            if parts[0].eq_ignore_ascii_case("interior") {
+                interior = color;
+            } else {
+                let position = parts[0]
+                    .parse::<f64>()
+                    .map_err(|e| ParsePaletteError::ParseFloatError(line_num, e))?;
+                if !position.is_finite() {
+                    return Err(ParsePaletteError::InvalidLine(line_num));
+                }
+                stops.push(PaletteStop { position, color });
+            }
+        }
+
+        if stops.is_empty() {
+            return Err(ParsePaletteError::NoStops);
+        }
+
+        stops.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
+
+        Ok(Palette { stops, interior })
+    }
+}
+
+impl TryFrom<UncheckedPalette> for Palette {
+    type Error = ParsePaletteError;
+
+    fn try_from(palette: UncheckedPalette) -> Result<Self, Self::Error> {
+        let mut stops = palette.stops;
+        if stops.is_empty() {
+            return Err(ParsePaletteError::NoStops);
+        }
+        if let Some(index) = stops.iter().position(|stop| !stop.position.is_finite()) {
+            return Err(ParsePaletteError::InvalidStop(index));
+        }
+
+        stops.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
+
+        Ok(Palette {
+            stops,
+            interior: palette.interior,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParsePaletteError {
+    NoStops,
+    InvalidLine(usize),
+    /// A deserialized stop, by index, has a position that isn't finite.
+    InvalidStop(usize),
+    ParseFloatError(usize, ParseFloatError),
+    ParseIntError(usize, ParseIntError),
+}
+
+impl Display for ParsePaletteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsePaletteError::NoStops => write!(f, "the palette has no stops"),
+            ParsePaletteError::InvalidLine(line) => write!(f, "invalid palette line {}", line),
+            ParsePaletteError::InvalidStop(index) => {
+                write!(f, "palette stop {} has an invalid position", index)
+            }
+            ParsePaletteError::ParseFloatError(line, e) => {
+                write!(f, "invalid position on palette line {}: {}", line, e)
+            }
+            ParsePaletteError::ParseIntError(line, e) => {
+                write!(f, "invalid color component on palette line {}: {}", line, e)
+            }
+        }
+    }
+}
+
+impl Error for ParsePaletteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParsePaletteError::ParseFloatError(_, e) => Some(e),
+            ParsePaletteError::ParseIntError(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use num_complex::Complex;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct View {
     pub image_width: u32,
     pub image_height: u32,
@@ -8,6 +9,9 @@ pub struct View {
     pub image_scale_y: f64,
     pub plane_start_x: f64,
     pub plane_start_y: f64,
+    /// The angle, in radians, the plane is rotated by about the center of the
+    /// image.
+    pub rotation: f64,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -17,55 +21,171 @@ pub enum ConstrainedValue<T> {
     GreaterThanConstraint,
 }
 
+impl<T> ConstrainedValue<T> {
+    /// Converts the value if it is within the constraint.
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> ConstrainedValue<U> {
+        match self {
+            ConstrainedValue::LessThanConstraint => ConstrainedValue::LessThanConstraint,
+            ConstrainedValue::WithinConstraint(value) => {
+                ConstrainedValue::WithinConstraint(f(value))
+            }
+            ConstrainedValue::GreaterThanConstraint => ConstrainedValue::GreaterThanConstraint,
+        }
+    }
+}
+
 impl View {
     pub fn new_uniform(image_width: u32, image_height: u32, plane_width: f64) -> View {
-        let image_scale = plane_width / image_width as f64;
-        let plane_height = image_height as f64 * image_scale;
+        View::new_centered(
+            image_width,
+            image_height,
+            plane_width,
+            Complex::<f64>::new(0f64, 0f64),
+        )
+    }
 
+    /// Creates a view covering an area of the plane with the given width and
+    /// height centered on the origin. The pixels are stretched if the plane's
+    /// aspect ratio doesn't match the image's.
+    pub fn new(image_width: u32, image_height: u32, plane_width: f64, plane_height: f64) -> View {
+        View::new_stretched(
+            image_width,
+            image_height,
+            plane_width,
+            plane_height,
+            Complex::<f64>::new(0f64, 0f64),
+        )
+    }
+
+    /// Creates a view with square pixels covering an area of the plane
+    /// centered on the given point.
+    pub fn new_centered(
+        image_width: u32,
+        image_height: u32,
+        plane_width: f64,
+        center: Complex<f64>,
+    ) -> View {
+        let plane_height = image_height as f64 * plane_width / image_width as f64;
+        View::new_stretched(image_width, image_height, plane_width, plane_height, center)
+    }
+
+    /// Creates a view covering an area of the plane with the given width and
+    /// height centered on the given point, like `new`.
+    pub fn new_stretched(
+        image_width: u32,
+        image_height: u32,
+        plane_width: f64,
+        plane_height: f64,
+        center: Complex<f64>,
+    ) -> View {
         View {
             image_width,
             image_height,
-            image_scale_x: image_scale,
-            image_scale_y: image_scale,
-            plane_start_x: -plane_width / 2f64,
-            plane_start_y: -plane_height / 2f64,
+            image_scale_x: plane_width / image_width as f64,
+            image_scale_y: plane_height / image_height as f64,
+            plane_start_x: center.re - plane_width / 2f64,
+            plane_start_y: center.im - plane_height / 2f64,
+            rotation: 0f64,
         }
     }
 
-    pub fn get_plane_coordinates(&self, (x, y): (u32, u32)) -> Complex<f64> {
+    /// Gets a copy of this view covering the same area of the plane with an
+    /// image `factor` times as wide and tall.
+    pub fn upscaled(self, factor: u32) -> View {
+        View {
+            image_width: self.image_width * factor,
+            image_height: self.image_height * factor,
+            image_scale_x: self.image_scale_x / factor as f64,
+            image_scale_y: self.image_scale_y / factor as f64,
+            ..self
+        }
+    }
+
+    /// Gets a copy of this view with the plane rotated by the given angle in
+    /// radians.
+    pub fn rotated(self, rotation: f64) -> View {
+        View { rotation, ..self }
+    }
+
+    /// Gets the point of the plane at the center of the image.
+    fn plane_center(&self) -> Complex<f64> {
         Complex::<f64>::new(
-            x as f64 * self.image_scale_x + self.plane_start_x,
-            y as f64 * self.image_scale_y + self.plane_start_y,
+            self.plane_start_x + self.image_width as f64 * self.image_scale_x / 2f64,
+            self.plane_start_y + self.image_height as f64 * self.image_scale_y / 2f64,
         )
     }
 
+    /// Rotates a point of the plane about the center of the image.
+    fn rotate(&self, point: Complex<f64>, rotation: f64) -> Complex<f64> {
+        if rotation == 0f64 {
+            return point;
+        }
+
+        let center = self.plane_center();
+        (point - center) * Complex::<f64>::from_polar(&1f64, &rotation) + center
+    }
+
+    pub fn get_plane_coordinates(&self, (x, y): (u32, u32)) -> Complex<f64> {
+        self.get_plane_coordinates_f64(x as f64, y as f64)
+    }
+
+    /// Gets the plane coordinates of a position in the image given in
+    /// fractional pixels, where pixel `n` covers `n` up to `n + 1`.
+    pub fn get_plane_coordinates_f64(&self, x: f64, y: f64) -> Complex<f64> {
+        self.rotate(
+            Complex::<f64>::new(
+                x * self.image_scale_x + self.plane_start_x,
+                y * self.image_scale_y + self.plane_start_y,
+            ),
+            self.rotation,
+        )
+    }
+
+    /// Gets the plane coordinates of a point offset from a pixel by a fraction
+    /// of a pixel.
+    pub fn get_plane_coordinates_offset(
+        &self,
+        (x, y): (u32, u32),
+        (offset_x, offset_y): (f64, f64),
+    ) -> Complex<f64> {
+        self.get_plane_coordinates_f64(x as f64 + offset_x, y as f64 + offset_y)
+    }
+
     pub fn get_pixel_coordinates(
         &self,
         plane_coordinates: Complex<f64>,
     ) -> (ConstrainedValue<u32>, ConstrainedValue<u32>) {
-        (
-            if plane_coordinates.re > self.plane_start_x {
-                let x = ((plane_coordinates.re - self.plane_start_x) / self.image_scale_x) as u32;
-
-                if x < self.image_width {
-                    ConstrainedValue::WithinConstraint(x)
-                } else {
-                    ConstrainedValue::GreaterThanConstraint
-                }
-            } else {
+        let (x, y) = self.get_subpixel_coordinates(plane_coordinates);
+        (x.map(|x| x as u32), y.map(|y| y as u32))
+    }
+
+    /// Gets the pixel coordinates of a point on the plane including how far
+    /// into the pixel it is, where pixel `n` covers `n` up to `n + 1`.
+    pub fn get_subpixel_coordinates(
+        &self,
+        plane_coordinates: Complex<f64>,
+    ) -> (ConstrainedValue<f64>, ConstrainedValue<f64>) {
+        let plane_coordinates = self.rotate(plane_coordinates, -self.rotation);
+
+        let constrain = |value: f64, size: u32| {
+            if value <= 0f64 {
                 ConstrainedValue::LessThanConstraint
-            },
-            if plane_coordinates.im > self.plane_start_y {
-                let y = ((plane_coordinates.im - self.plane_start_y) / self.image_scale_y) as u32;
-
-                if y < self.image_height {
-                    ConstrainedValue::WithinConstraint(y)
-                } else {
-                    ConstrainedValue::GreaterThanConstraint
-                }
+            } else if value < size as f64 {
+                ConstrainedValue::WithinConstraint(value)
             } else {
-                ConstrainedValue::LessThanConstraint
-            },
+                ConstrainedValue::GreaterThanConstraint
+            }
+        };
+
+        (
+            constrain(
+                (plane_coordinates.re - self.plane_start_x) / self.image_scale_x,
+                self.image_width,
+            ),
+            constrain(
+                (plane_coordinates.im - self.plane_start_y) / self.image_scale_y,
+                self.image_height,
+            ),
         )
     }
 }
@@ -0,0 +1,20 @@
+#![feature(try_trait)]
+
+//! Generates Julia sets and related fractals into RGBA image buffers, and
+//! writes them out as images or videos.
+//!
+//! A frame is generated by creating a `ValueGenerator` for a `View` of the
+//! plane and passing it to `generate_fractal` along with a buffer to fill.
+
+pub mod generator;
+pub mod output;
+pub mod raster;
+
+pub use generator::{
+    generate_fractal, generate_fractal_16, view::View, Color, FractalGenerationError,
+    FractalThreadPool, RGBA16Color, RGBAColor, ValueGenerator,
+};
+pub use output::{
+    EncoderSettings, FrameSink, MediaOutput, MediaOutputCreationError, MediaWriteError, NullOutput,
+    PngSequenceOutput, RawOutput, StillImageOutput,
+};
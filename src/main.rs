@@ -1,153 +1,631 @@
-#![feature(try_trait)]
-
-use ffmpeg4::{format, frame};
+use indicatif::{ProgressBar, ProgressStyle};
+use julia_in_motion::{generator, output, raster};
+use log::{debug, info, warn};
 use num_complex::Complex;
 use rusttype::{Font, Scale};
-use std::time::{Duration, Instant};
+use std::{
+    cell::Cell,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io::{self, Write},
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, sync_channel},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 mod args;
-mod generator;
-mod output;
+mod expression;
+mod metadata;
+mod minimap;
 mod path_util;
-mod raster;
 mod util;
 
 const FONT_DATA: &[u8] = include_bytes!("OxygenMono-Regular.ttf");
 
+/// The height of the strip written by `--palette-preview`.
+const PALETTE_PREVIEW_HEIGHT: u32 = 32;
+
 fn main() {
-    let cmd_args = args::CmdArgs::load().expect("Error parsing commandline args");
+    if let Err(e) = run() {
+        eprintln!("Error: {}", describe_error(e.as_ref()));
+        process::exit(1);
+    }
+}
+
+/// Describes an error along with each of its causes. Many errors already
+/// include their cause in their message, so causes that are already described
+/// aren't repeated.
+fn describe_error(error: &dyn Error) -> String {
+    let mut description = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        let message = cause.to_string();
+        if !description.contains(&message) {
+            description = format!("{}: {}", description, message);
+        }
+        source = cause.source();
+    }
+
+    description
+}
+
+/// Sets up and runs the application, returning any error so it can be
+/// reported without a panic.
+fn run() -> Result<(), Box<dyn Error>> {
+    if args::list_codecs_requested() {
+        print_video_encoders();
+        return Ok(());
+    }
+
+    let mut cmd_args = args::CmdArgs::load()?;
+
+    // RUST_LOG can still override the level chosen by -v and --quiet
+    env_logger::Builder::new()
+        .filter_level(cmd_args.log_level)
+        .parse_default_env()
+        .init();
 
-    let font = Font::from_bytes(FONT_DATA).expect("Error loading font");
+    let font = match cmd_args.font.take() {
+        Some(font_data) => Font::from_bytes(font_data)?,
+        None => Font::from_bytes(FONT_DATA)?,
+    };
 
-    let mut app = Application::new(cmd_args, font).expect("Error creating the application");
+    if cmd_args.dry_run {
+        print_dry_run(&cmd_args);
+        return Ok(());
+    }
+
+    if let Some(path) = &cmd_args.palette_preview {
+        write_palette_preview(&cmd_args, path)?;
+        return Ok(());
+    }
 
-    app.run().expect("Error running the application");
+    if cmd_args.write_metadata {
+        metadata::write_metadata(&cmd_args)?;
+    }
+
+    if let args::CPath::Svg(path) = &cmd_args.c_path {
+        debug!(
+            "Path length: {}",
+            path_util::approximate_path_length(path.as_slice(), cmd_args.path_tolerance)
+        );
+    }
+    info!(
+        "Rendering frames {} to {} of {}",
+        cmd_args.frame_range.start,
+        cmd_args.frame_range.end - 1,
+        cmd_args.frames
+    );
+
+    let mut app = Application::new(cmd_args, font)?;
+
+    // stop generating on Ctrl-C but still finish writing the video
+    let cancel = app.cancel.clone();
+    ctrlc::set_handler(move || cancel.store(true, Ordering::Relaxed))?;
+
+    app.run()?;
+
+    Ok(())
 }
 
 struct Application<'a> {
     view: generator::view::View,
-    iterations: u32,
-    smoothing: generator::args::Smoothing,
-    mandelbrot: bool,
+    center: Complex<f64>,
+    /// The height of the plane relative to its width, if it was given
+    /// explicitly instead of keeping the pixels square.
+    plane_aspect: Option<f64>,
+    zoom: Option<(f64, f64)>,
+    iteration_ramp: Option<(u32, u32)>,
+    params: generator::args::FractalParams,
+    coloring: generator::args::ColoringParams,
+    crosshair_color: generator::RGBAColor,
+    crosshair_thickness: u32,
+    antialias_crosshair: bool,
+    marker: args::Marker,
+    marker_radius: f64,
+    label_format: Option<String>,
+    label_size: f32,
+    /// The text of the title card and how many frames it is shown for.
+    title: Option<(String, u32)>,
+    /// The text of the end card and how many frames it is shown for.
+    end_card: Option<(String, u32)>,
+    card_color: generator::RGBAColor,
+    card_text_size: f32,
+    frame_width: u32,
+    minimap: bool,
+    split: bool,
+    path_overlay: bool,
+    orbit_trail: bool,
+    orbit_seed: Complex<f64>,
+    orbit_color: generator::RGBAColor,
+    allow_partial_frames: bool,
+    stats_overlay: bool,
+    frame_number_overlay: bool,
+    fps: ffmpeg4::Rational,
+    num_threads: usize,
+    parallel_frames: usize,
+    pipeline: bool,
+    /// How many times as wide and tall fractals are generated before they are
+    /// shrunk down to the size of a frame.
+    downsample: u32,
+    pool: Arc<generator::FractalThreadPool>,
+    cancel: Arc<AtomicBool>,
     font: Font<'a>,
-    media_out: output::MediaOutput,
+    media_out: Box<dyn output::FrameSink>,
+    output_distance: Option<PathBuf>,
     frames: u32,
-    path: lyon_path::Path,
-    path_tolerance: f32,
-    step_length: f32,
+    frame_range: Range<u32>,
+    /// The number of frames along the path, which is less than `frames` when
+    /// the path is played back in reverse after it.
+    path_frames: u32,
+    /// The frames between the ends of the path, kept without their frame
+    /// number and stats overlays along with how long each took to generate,
+    /// to be written again in reverse when a boomerang is buffered.
+    boomerang_buffer: Option<Vec<(Box<[u8]>, Duration)>>,
+    fade_in: u32,
+    fade_out: u32,
+    points: Vec<Complex<f64>>,
     video_progress_interval: Duration,
     fractal_progress_interval: Duration,
+    progress_format: args::ProgressFormat,
+    progress_bar: ProgressBar,
+    benchmark: bool,
+    /// Where a still image with 16 bits per channel is written, if one was
+    /// asked for instead of the usual 8-bit frames.
+    output_16: Option<PathBuf>,
+    generation_time: Cell<Duration>,
+    encoding_time: Duration,
+    pixels_generated: Cell<u64>,
+    frames_written: u32,
 }
 
 impl Application<'_> {
     pub fn new(args: args::CmdArgs, font: Font) -> Result<Application, ApplicationCreationError> {
-        // open the media output
-        let media_out = output::MediaOutput::new(
-            &args.output,
-            args.image_width,
-            args.image_height,
-            args.time_base,
-        )?;
+        // split frames hold two panes side by side
+        let frame_width = if args.split {
+            args.image_width * 2
+        } else {
+            args.image_width
+        };
 
-        // walk along the path to determine its length
-        let path_length =
-            path_util::approximate_path_length(args.path.as_slice(), args.path_tolerance);
+        // check the memory limit before the output is created, so huge frames fail early
+        let parallel_frames = match args.max_memory {
+            Some(max_memory) => limit_parallel_frames(&args, frame_width, max_memory)?,
+            None => args.parallel_frames,
+        };
 
-        // get the length of each step
-        let step_length = path_length / args.frames as f32;
+        let media_out = open_output(&args, frame_width)?;
+
+        let plane_aspect = args
+            .plane_height
+            .map(|plane_height| plane_height / args.plane_width);
+
+        let title_frames = duration_frames(args.title_duration, args.fps);
+        let end_card_frames = duration_frames(args.end_card_duration, args.fps);
+
+        let points = frame_points(&args);
+
+        let num_threads = if args.threads == 0 {
+            num_cpus::get() + 2
+        } else {
+            args.threads
+        };
+        debug!("Using {} fractal threads", num_threads);
+
+        let progress_bar = match args.progress_format {
+            args::ProgressFormat::Human => ProgressBar::new(args.frame_range.len() as u64),
+            args::ProgressFormat::Json => ProgressBar::hidden(),
+        };
+        progress_bar.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    "{elapsed_precise} [{bar:40}] {pos}/{len} frames ({per_sec}, ETA {eta}) {msg}",
+                )
+                .progress_chars("=> "),
+        );
 
         Ok(Application {
-            view: generator::view::View::new_uniform(
+            view: centered_view(
                 args.image_width,
                 args.image_height,
                 args.plane_width,
-            ),
-            iterations: args.iterations,
-            smoothing: args.smoothing,
-            mandelbrot: args.mandelbrot,
+                plane_aspect,
+                args.center,
+            )
+            .rotated(args.rotation.to_radians()),
+            center: args.center,
+            plane_aspect,
+            zoom: args.zoom,
+            iteration_ramp: args.iteration_ramp,
+            params: fractal_params(&args),
+            coloring: coloring_params(&args),
+            crosshair_color: args.crosshair_color,
+            crosshair_thickness: args.crosshair_thickness,
+            antialias_crosshair: args.antialias_crosshair,
+            marker: args.marker,
+            marker_radius: args.marker_radius,
+            label_format: args.label_format,
+            label_size: args.label_size,
+            title: args.title.map(|text| (text, title_frames)),
+            end_card: args.end_card.map(|text| (text, end_card_frames)),
+            card_color: args.card_color,
+            card_text_size: args.card_text_size,
+            frame_width,
+            minimap: args.minimap,
+            split: args.split,
+            path_overlay: args.path_overlay,
+            orbit_trail: args.orbit_trail,
+            orbit_seed: args.orbit_seed,
+            orbit_color: args.orbit_color,
+            allow_partial_frames: args.allow_partial_frames,
+            stats_overlay: args.stats_overlay,
+            frame_number_overlay: args.frame_number_overlay,
+            fps: args.fps,
+            num_threads,
+            parallel_frames,
+            pipeline: args.pipeline,
+            downsample: args.downsample,
+            pool: Arc::new(generator::FractalThreadPool::new(num_threads)),
+            cancel: Arc::new(AtomicBool::new(false)),
             font,
             media_out,
+            output_distance: args.output_distance,
             frames: args.frames,
-            path: args.path,
-            path_tolerance: args.path_tolerance,
-            step_length,
+            frame_range: args.frame_range,
+            path_frames: args.path_frames,
+            boomerang_buffer: if args.boomerang == Some(args::BoomerangMode::Buffer) {
+                Some(vec![])
+            } else {
+                None
+            },
+            fade_in: args.fade_in,
+            fade_out: args.fade_out,
+            points,
             video_progress_interval: args.video_progress_interval,
             fractal_progress_interval: args.fractal_progress_interval,
+            progress_format: args.progress_format,
+            progress_bar,
+            benchmark: args.benchmark,
+            output_16: if args.bit_depth == 16 {
+                Some(args.output.clone())
+            } else {
+                None
+            },
+            generation_time: Cell::new(Duration::default()),
+            encoding_time: Duration::default(),
+            pixels_generated: Cell::new(0),
+            frames_written: 0,
         })
     }
 
     pub fn run(&mut self) -> Result<(), ApplicationRunError> {
+        let run_start = Instant::now();
+
+        if let Some(output_distance) = &self.output_distance {
+            self.write_distance_image(output_distance)?;
+        }
+
         self.media_out.start()?;
 
-        if self.mandelbrot {
-            self.render_mandelbrot()?;
+        // the cards are only written when the part of the video they border is rendered
+        if self.frame_range.start == 0 {
+            if let Some((text, frames)) = self.title.clone() {
+                self.write_card(&text, 0, frames)?;
+            }
+        }
+
+        let result = if let Some(output_16) = self.output_16.clone() {
+            self.render_still_16(&output_16)
+        } else if self.split {
+            self.render_split()
+        } else if self.params.fractal_type.is_julia() {
+            self.render_julia()
         } else {
-            self.render_julia()?;
+            self.render_mandelbrot()
+        };
+
+        // the backward half of a buffered boomerang comes from the frames rendered going forward
+        let result = result.and_then(|()| self.write_buffered_boomerang());
+
+        match result {
+            Ok(()) => {
+                self.progress_bar.finish();
+
+                if self.frame_range.end == self.frames {
+                    if let Some((text, frames)) = self.end_card.clone() {
+                        let first_frame = self.title_frames() + self.frames;
+                        self.write_card(&text, first_frame, frames)?;
+                    }
+                }
+            }
+            Err(ApplicationRunError::FractalGenerationError(
+                generator::FractalGenerationError::Cancelled,
+            )) => {
+                self.progress_bar.abandon();
+                warn!("Cancelled, finishing the video early");
+            }
+            Err(e) => {
+                self.progress_bar.abandon();
+                return Err(e);
+            }
         }
 
         self.media_out.finish()?;
 
+        if self.benchmark {
+            self.print_benchmark(run_start.elapsed());
+        }
+
         Ok(())
     }
 
-    /// Renders the video as a Mandelbrot set with crosshairs tracing a path
-    /// along it.
-    fn render_mandelbrot(&mut self) -> Result<(), ApplicationRunError> {
-        let generator = generator::ValueGenerator::new(
-            self.view,
-            true,
-            self.iterations,
-            self.smoothing,
-            Complex::<f64>::new(0f64, 0f64),
-        );
+    /// Gets which frame along the path a frame of the video shows, which
+    /// counts back down when a boomerang plays the path in reverse.
+    fn path_position(&self, frame_num: u32) -> u32 {
+        if frame_num < self.path_frames {
+            frame_num
+        } else {
+            (self.path_frames - 1) * 2 - frame_num
+        }
+    }
 
-        let mandelbrot_image = generator::generate_fractal(
-            &generator,
-            num_cpus::get() + 2,
-            |progress| self.fractal_progress_callback(progress),
-            self.fractal_progress_interval,
-        )?;
+    /// Gets the frames that are rendered, which leaves out the backward half
+    /// of a buffered boomerang.
+    fn rendered_frames(&self) -> Range<u32> {
+        if self.boomerang_buffer.is_some() {
+            self.frame_range.start..self.frame_range.end.min(self.path_frames)
+        } else {
+            self.frame_range.clone()
+        }
+    }
+
+    /// Writes the backward half of a buffered boomerang from the frames kept
+    /// while going forward, in reverse.
+    fn write_buffered_boomerang(&mut self) -> Result<(), ApplicationRunError> {
+        let buffer = match self.boomerang_buffer.take() {
+            Some(buffer) => buffer,
+            None => return Ok(()),
+        };
+
+        for (frame_num, (mut image, frame_time)) in
+            (self.path_frames..self.frames).zip(buffer.into_iter().rev())
+        {
+            self.write_frame(frame_num, &mut image, frame_time)?;
+            self.video_progress_callback(frame_num);
+        }
+
+        Ok(())
+    }
+
+    /// Gets how far along the path a frame is, from 0 at the first frame to 1
+    /// at the last.
+    fn path_progress(&self, frame_num: u32) -> f64 {
+        if self.path_frames > 1 {
+            self.path_position(frame_num) as f64 / (self.path_frames - 1) as f64
+        } else {
+            0f64
+        }
+    }
+
+    /// Gets the fractal parameters for a specific frame, ramping the
+    /// iterations if a ramp was requested.
+    fn params_at(&self, frame_num: u32) -> generator::args::FractalParams {
+        match self.iteration_ramp {
+            Some((start, end)) => {
+                let t = self.path_progress(frame_num);
+                generator::args::FractalParams {
+                    iterations: (start as f64 + (end as f64 - start as f64) * t).round() as u32,
+                    ..self.params
+                }
+            }
+            None => self.params,
+        }
+    }
 
-        let mut frame = frame::Video::new(
-            format::Pixel::RGBA,
+    /// Gets the view of the plane for a specific frame, zooming between the
+    /// start and end plane widths if a zoom was requested.
+    fn view_at(&self, frame_num: u32) -> generator::view::View {
+        match self.zoom {
+            Some((start_width, end_width)) => {
+                let t = self.path_progress(frame_num);
+
+                // zoom geometrically so the apparent zoom speed stays constant
+                centered_view(
+                    self.view.image_width,
+                    self.view.image_height,
+                    start_width * (end_width / start_width).powf(t),
+                    self.plane_aspect,
+                    self.center,
+                )
+                .rotated(self.view.rotation)
+            }
+            None => self.view,
+        }
+    }
+
+    /// Writes a still image of the estimated distance to the boundary of the
+    /// fractal at the start of the video.
+    fn write_distance_image(&self, path: &Path) -> Result<(), ApplicationRunError> {
+        let c = if self.params.fractal_type.is_julia() {
+            self.points
+                .first()
+                .cloned()
+                .unwrap_or_else(|| Complex::<f64>::new(0f64, 0f64))
+        } else {
+            Complex::<f64>::new(0f64, 0f64)
+        };
+
+        let generator =
+            generator::ValueGenerator::new(self.view, self.params, self.coloring.clone(), c);
+
+        let distance_image =
+            generator::generate_distance_image(&generator, &self.pool, &self.cancel)?;
+
+        output::write_image(
+            &path,
             self.view.image_width,
             self.view.image_height,
-        );
-        let mut frame_num = 0;
+            &distance_image,
+        )?;
+
+        Ok(())
+    }
+
+    /// Renders the video as a Mandelbrot set (or another fractal that takes
+    /// `c` from the plane) with crosshairs tracing a path along it.
+    fn render_mandelbrot(&mut self) -> Result<(), ApplicationRunError> {
+        let mut background = BackgroundCache::new(self.params);
+        let mut current_image = self.new_image_buffer();
+
+        let minimap = if self.minimap {
+            minimap::Minimap::new(
+                self.view.image_width,
+                self.view.image_height,
+                self.params,
+                self.coloring.clone(),
+                &self.pool,
+                &self.cancel,
+            )?
+        } else {
+            None
+        };
+
         let mut previous_progress = Instant::now();
 
-        let points =
-            path_util::path_points(self.path.as_slice(), self.path_tolerance, self.step_length);
+        for frame_num in self.rendered_frames() {
+            let complex = self.points[frame_num as usize];
+
+            if self.cancel.load(Ordering::Relaxed) {
+                return Err(generator::FractalGenerationError::Cancelled.into());
+            }
 
-        for position in points {
-            frame.set_pts(Some(frame_num as i64));
-            let mut current_image = mandelbrot_image.clone();
+            let frame_start = Instant::now();
+            let view = self.view_at(frame_num);
 
-            let complex = Complex::<f64>::new(position.x as f64, position.y as f64);
-            let (pixel_x, pixel_y) = self.view.get_pixel_coordinates(complex);
+            self.draw_background(&mut background, &view, frame_num, &mut current_image)?;
 
-            raster::draw_constrained_crosshair(
+            self.draw_mandelbrot_overlays(
                 &mut current_image,
-                self.view.image_width,
-                self.view.image_height,
-                (pixel_x, pixel_y),
+                &view,
+                frame_num,
+                complex,
+                minimap.as_ref(),
             );
 
-            let complex_str = format!("{:.5} + {:.5}i", complex.re, complex.im);
-            raster::draw_constrained_glyph_line(
-                &mut current_image,
-                self.view.image_width,
-                self.view.image_height,
-                &self.font,
-                Scale::uniform(12f32),
-                (pixel_x, pixel_y),
-                4f32,
-                &complex_str,
+            self.write_frame(frame_num, &mut current_image, frame_start.elapsed())?;
+
+            // call the progress callback every now and then
+            let now = Instant::now();
+            if now.saturating_duration_since(previous_progress) > self.video_progress_interval {
+                self.video_progress_callback(frame_num);
+                previous_progress = now;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renders the video as a Mandelbrot set with crosshairs on the left and the
+    /// Julia set for the point under the crosshairs on the right.
+    fn render_split(&mut self) -> Result<(), ApplicationRunError> {
+        let pane_width = self.view.image_width;
+        let pane_height = self.view.image_height;
+
+        let mandelbrot_params = generator::args::FractalParams {
+            fractal_type: generator::args::FractalType::Mandelbrot,
+            ..self.params
+        };
+        let julia_params = generator::args::FractalParams {
+            fractal_type: generator::args::FractalType::Julia,
+            ..self.params
+        };
+
+        let mut background = BackgroundCache::new(mandelbrot_params);
+        let mut mandelbrot_image = self.new_image_buffer();
+        let mut julia_image = self.new_image_buffer();
+        let mut frame = vec![0u8; pane_width as usize * 2 * pane_height as usize * 4];
+
+        let minimap = if self.minimap {
+            minimap::Minimap::new(
+                pane_width,
+                pane_height,
+                mandelbrot_params,
+                self.coloring.clone(),
+                &self.pool,
+                &self.cancel,
+            )?
+        } else {
+            None
+        };
+
+        let mut previous_progress = Instant::now();
+
+        for frame_num in self.rendered_frames() {
+            let complex = self.points[frame_num as usize];
+
+            if self.cancel.load(Ordering::Relaxed) {
+                return Err(generator::FractalGenerationError::Cancelled.into());
+            }
+
+            let frame_start = Instant::now();
+            let view = self.view_at(frame_num);
+
+            self.draw_background(&mut background, &view, frame_num, &mut mandelbrot_image)?;
+
+            self.draw_mandelbrot_overlays(
+                &mut mandelbrot_image,
+                &view,
+                frame_num,
+                complex,
+                minimap.as_ref(),
             );
 
-            frame.data_mut(0).copy_from_slice(&current_image);
+            let frame_params = generator::args::FractalParams {
+                iterations: self.params_at(frame_num).iterations,
+                ..julia_params
+            };
+            let generator =
+                generator::ValueGenerator::new(view, frame_params, self.coloring.clone(), complex);
+            self.generate_image(&generator, &mut julia_image)?;
 
-            self.media_out.write_frame(&frame)?;
+            if self.orbit_trail {
+                self.draw_orbit_trail(
+                    &mut julia_image,
+                    &view,
+                    frame_params,
+                    self.orbit_seed,
+                    complex,
+                );
+            }
+
+            raster::draw_image(
+                &mut frame,
+                pane_width * 2,
+                pane_height,
+                &mandelbrot_image,
+                pane_width,
+                pane_height,
+                (0, 0),
+            );
+            raster::draw_image(
+                &mut frame,
+                pane_width * 2,
+                pane_height,
+                &julia_image,
+                pane_width,
+                pane_height,
+                (pane_width, 0),
+            );
+
+            self.write_frame(frame_num, &mut frame, frame_start.elapsed())?;
 
             // call the progress callback every now and then
             let now = Instant::now();
@@ -155,79 +633,1085 @@ impl Application<'_> {
                 self.video_progress_callback(frame_num);
                 previous_progress = now;
             }
+        }
 
-            frame_num += 1;
+        Ok(())
+    }
+
+    /// Copies the background fractal for a view into the image, only
+    /// generating it if the view or iterations changed since it was last
+    /// generated. The iterations only ramp while zooming, so a still
+    /// background is generated once.
+    fn draw_background(
+        &self,
+        background: &mut BackgroundCache,
+        view: &generator::view::View,
+        frame_num: u32,
+        image: &mut [u8],
+    ) -> Result<(), ApplicationRunError> {
+        let iterations = if self.zoom.is_some() {
+            self.params_at(frame_num).iterations
+        } else {
+            background.params.iterations
+        };
+
+        match &background.cached {
+            Some((cached_view, cached_iterations, cached_image))
+                if cached_view == view && *cached_iterations == iterations =>
+            {
+                image.copy_from_slice(cached_image);
+            }
+            _ => {
+                let generator = generator::ValueGenerator::new(
+                    *view,
+                    generator::args::FractalParams {
+                        iterations,
+                        ..background.params
+                    },
+                    self.coloring.clone(),
+                    Complex::<f64>::new(0f64, 0f64),
+                );
+                self.generate_image(&generator, image)?;
+                background.cached = Some((*view, iterations, Box::from(&*image)));
+            }
         }
 
         Ok(())
     }
 
+    /// Draws the path, crosshairs, coordinate label, and minimap over a
+    /// Mandelbrot image for the given frame.
+    fn draw_mandelbrot_overlays(
+        &self,
+        image: &mut [u8],
+        view: &generator::view::View,
+        frame_num: u32,
+        complex: Complex<f64>,
+        minimap: Option<&minimap::Minimap>,
+    ) {
+        if self.path_overlay {
+            raster::draw_path_overlay(image, view, &self.points, frame_num as usize);
+        }
+
+        // split videos show a Mandelbrot set here even when the fractal is a Julia set
+        if self.orbit_trail {
+            let params = generator::args::FractalParams {
+                fractal_type: if self.params.fractal_type.is_julia() {
+                    generator::args::FractalType::Mandelbrot
+                } else {
+                    self.params.fractal_type
+                },
+                ..self.params
+            };
+            self.draw_orbit_trail(image, view, params, complex, complex);
+        }
+
+        let (pixel_x, pixel_y) = view.get_pixel_coordinates(complex);
+
+        if self.marker.has_crosshair() {
+            if self.antialias_crosshair {
+                raster::draw_constrained_antialiased_crosshair(
+                    image,
+                    view.image_width,
+                    view.image_height,
+                    view.get_subpixel_coordinates(complex),
+                    self.crosshair_color,
+                    self.crosshair_thickness,
+                );
+            } else {
+                raster::draw_constrained_crosshair(
+                    image,
+                    view.image_width,
+                    view.image_height,
+                    (pixel_x, pixel_y),
+                    self.crosshair_color,
+                    self.crosshair_thickness,
+                );
+            }
+        }
+
+        if self.marker.has_dot() {
+            raster::draw_marker_dot(
+                image,
+                view.image_width,
+                view.image_height,
+                view.get_subpixel_coordinates(complex),
+                self.marker_radius,
+                self.crosshair_color,
+            );
+        }
+
+        if let Some(label_format) = &self.label_format {
+            raster::draw_constrained_glyph_line(
+                image,
+                view.image_width,
+                view.image_height,
+                &self.font,
+                Scale::uniform(self.label_size),
+                (pixel_x, pixel_y),
+                4f32,
+                &util::format_complex(label_format, complex),
+            );
+        }
+
+        if let Some(minimap) = minimap {
+            minimap.draw(image, view.image_width, view.image_height, view);
+        }
+    }
+
     /// Renders the video as a Julia set following the specified path along the
     /// Mandelbrot set.
     fn render_julia(&mut self) -> Result<(), ApplicationRunError> {
-        let mut frame = frame::Video::new(
-            format::Pixel::RGBA,
-            self.view.image_width,
-            self.view.image_height,
-        );
-        let mut frame_num = 0;
+        if self.pipeline || self.parallel_frames > 1 {
+            return self.render_julia_pipelined();
+        }
+
         let mut previous_progress = Instant::now();
 
-        let points =
-            path_util::path_points(self.path.as_slice(), self.path_tolerance, self.step_length);
+        // every frame is generated into the same buffer
+        let mut julia_image = self.new_image_buffer();
 
-        for position in points {
-            frame.set_pts(Some(frame_num as i64));
+        for frame_num in self.rendered_frames() {
+            let c = self.points[frame_num as usize];
+            let frame_start = Instant::now();
 
             let generator = generator::ValueGenerator::new(
-                self.view,
-                false,
-                self.iterations,
-                self.smoothing,
-                Complex::<f64>::new(position.x as f64, position.y as f64),
+                self.view_at(frame_num),
+                self.params_at(frame_num),
+                self.coloring.clone(),
+                c,
             );
 
-            let julia_image = generator::generate_fractal(
-                &generator,
-                num_cpus::get() + 2,
-                |progress| self.fractal_progress_callback(progress),
-                self.fractal_progress_interval,
+            self.generate_image(&generator, &mut julia_image)?;
+
+            self.write_julia_frame(
+                frame_num,
+                &mut julia_image,
+                frame_start.elapsed(),
+                &mut previous_progress,
             )?;
+        }
+
+        Ok(())
+    }
 
-            frame.data_mut(0).copy_from_slice(&julia_image);
+    /// Renders the video as a Julia set like `render_julia`, but generates
+    /// frames on a separate thread while this one writes them, so encoding
+    /// doesn't hold up generation. Frames are generated in windows of
+    /// `parallel_frames` at once, and each window is finished before the next
+    /// one starts so the frames arrive in order.
+    fn render_julia_pipelined(&mut self) -> Result<(), ApplicationRunError> {
+        let window_size = self.parallel_frames;
 
-            self.media_out.write_frame(&frame)?;
+        let generators: Vec<_> = self
+            .rendered_frames()
+            .map(|frame_num| {
+                generator::ValueGenerator::new(
+                    self.view_at(frame_num),
+                    self.params_at(frame_num),
+                    self.coloring.clone(),
+                    self.points[frame_num as usize],
+                )
+            })
+            .collect();
 
-            // call the progress callback every now and then
-            let now = Instant::now();
-            if now.saturating_duration_since(previous_progress) > self.video_progress_interval {
-                self.video_progress_callback(frame_num);
-                previous_progress = now;
+        // one window can be generated while the previous one is written, and
+        // buffers are handed back for reuse once they have been written
+        let (frame_tx, frame_rx) = sync_channel(window_size);
+        let (buffer_tx, buffer_rx) = channel::<Box<[u8]>>();
+        for _ in 0..window_size * 2 {
+            buffer_tx.send(self.new_image_buffer()).unwrap();
+        }
+
+        let pool = self.pool.clone();
+        let cancel = self.cancel.clone();
+        let progress_interval = self.fractal_progress_interval;
+        let downsample = self.downsample;
+
+        // the generator thread stops once this thread hangs up either channel
+        thread::Builder::new()
+            .name("Frame Generator".to_owned())
+            .spawn(move || {
+                // a fixed set of workers, one per frame of a window, takes
+                // frames from a shared queue and stops once it is hung up
+                let (job_tx, job_rx) = channel::<(usize, generator::ValueGenerator, Box<[u8]>)>();
+                let (done_tx, done_rx) = channel();
+                let job_rx = Arc::new(Mutex::new(job_rx));
+                for worker in 0..window_size {
+                    let job_rx = job_rx.clone();
+                    let done_tx = done_tx.clone();
+                    let pool = pool.clone();
+                    let cancel = cancel.clone();
+
+                    thread::Builder::new()
+                        .name(format!("Frame Worker {}", worker))
+                        .spawn(move || loop {
+                            let job = job_rx.lock().unwrap().recv();
+                            let (index, generator, mut image) = match job {
+                                Ok(job) => job,
+                                Err(_) => return,
+                            };
+
+                            // per-frame progress would be interleaved, so it isn't reported
+                            let frame = panic::catch_unwind(AssertUnwindSafe(|| {
+                                let frame_start = Instant::now();
+                                let result = generate_downsampled_fractal(
+                                    &generator,
+                                    &mut image,
+                                    &pool,
+                                    &cancel,
+                                    |_| {},
+                                    progress_interval,
+                                    downsample,
+                                );
+                                (result, frame_start.elapsed())
+                            }))
+                            .map(|(result, frame_time)| (image, result, frame_time))
+                            .map_err(|payload| {
+                                generator::FractalGenerationError::ThreadPanicked(
+                                    generator::panic_message(payload),
+                                )
+                            });
+
+                            if done_tx.send((index, frame)).is_err() {
+                                return;
+                            }
+                        })
+                        .unwrap();
+                }
+                // only the workers can report finished frames, so a window
+                // can't wait forever on workers that are gone
+                drop(done_tx);
+
+                for window in generators.chunks(window_size) {
+                    for (index, generator) in window.iter().enumerate() {
+                        let image = match buffer_rx.recv() {
+                            Ok(image) => image,
+                            Err(_) => return,
+                        };
+                        if job_tx.send((index, generator.clone(), image)).is_err() {
+                            return;
+                        }
+                    }
+
+                    // frames can finish in any order, but are written in order
+                    let mut frames: Vec<_> = window.iter().map(|_| None).collect();
+                    for _ in window {
+                        match done_rx.recv() {
+                            Ok((index, frame)) => frames[index] = Some(frame),
+                            Err(_) => return,
+                        }
+                    }
+
+                    for frame in frames {
+                        if frame_tx.send(frame.unwrap()).is_err() {
+                            return;
+                        }
+                    }
+                }
+            })
+            .unwrap();
+
+        let mut previous_progress = Instant::now();
+
+        for frame_num in self.rendered_frames() {
+            let (mut image, result, frame_time) = frame_rx.recv().map_err(|_| {
+                generator::FractalGenerationError::ThreadPanicked(
+                    "the frame generator thread stopped early".to_owned(),
+                )
+            })??;
+            self.record_generation(frame_time);
+            self.accept_generation_result(result)?;
+
+            self.write_julia_frame(frame_num, &mut image, frame_time, &mut previous_progress)?;
+
+            // the generator thread may already be finished with its buffers
+            let _ = buffer_tx.send(image);
+        }
+
+        Ok(())
+    }
+
+    /// Draws the overlays onto a generated Julia frame, writes it, and reports
+    /// progress every now and then.
+    fn write_julia_frame(
+        &mut self,
+        frame_num: u32,
+        image: &mut [u8],
+        frame_time: Duration,
+        previous_progress: &mut Instant,
+    ) -> Result<(), ApplicationRunError> {
+        if self.orbit_trail {
+            self.draw_orbit_trail(
+                image,
+                &self.view_at(frame_num),
+                self.params_at(frame_num),
+                self.orbit_seed,
+                self.points[frame_num as usize],
+            );
+        }
+
+        self.write_frame(frame_num, image, frame_time)?;
+
+        // call the progress callback every now and then
+        let now = Instant::now();
+        if now.saturating_duration_since(*previous_progress) > self.video_progress_interval {
+            self.video_progress_callback(frame_num);
+            *previous_progress = now;
+        }
+
+        Ok(())
+    }
+
+    /// Allocates an image buffer the size of a frame.
+    fn new_image_buffer(&self) -> Box<[u8]> {
+        vec![0u8; self.view.image_width as usize * self.view.image_height as usize * 4]
+            .into_boxed_slice()
+    }
+
+    /// Renders a single still image with 16 bits per channel straight to a
+    /// file. None of the overlays are drawn, as they only work on 8-bit images.
+    fn render_still_16(&mut self, path: &Path) -> Result<(), ApplicationRunError> {
+        let generator = generator::ValueGenerator::new(
+            self.view,
+            self.params,
+            self.coloring.clone(),
+            self.points[0],
+        );
+        let mut image =
+            vec![0u16; self.view.image_width as usize * self.view.image_height as usize * 4];
+
+        let generation_start = Instant::now();
+        let result = generator::generate_fractal_16(
+            &generator,
+            &mut image,
+            &self.pool,
+            &self.cancel,
+            |progress| self.fractal_progress_callback(progress),
+            self.fractal_progress_interval,
+        );
+        self.record_generation(generation_start.elapsed());
+        self.accept_generation_result(result)?;
+
+        output::write_image_16(&path, self.view.image_width, self.view.image_height, &image)?;
+        self.video_progress_callback(0);
+
+        Ok(())
+    }
+
+    /// Generates a single fractal image, using a partially generated image if
+    /// some of the fractal threads failed and partial frames are allowed.
+    fn generate_image(
+        &self,
+        generator: &generator::ValueGenerator,
+        image: &mut [u8],
+    ) -> Result<(), ApplicationRunError> {
+        let generation_start = Instant::now();
+        let result = generate_downsampled_fractal(
+            generator,
+            image,
+            &self.pool,
+            &self.cancel,
+            |progress| self.fractal_progress_callback(progress),
+            self.fractal_progress_interval,
+            self.downsample,
+        );
+        self.record_generation(generation_start.elapsed());
+
+        self.accept_generation_result(result)
+    }
+
+    /// Adds a generated fractal image to the benchmark statistics.
+    fn record_generation(&self, elapsed: Duration) {
+        let supersample = self.params.supersample as u64 * self.downsample as u64;
+        let pixels = self.view.image_width as u64
+            * self.view.image_height as u64
+            * supersample
+            * supersample;
+
+        self.generation_time
+            .set(self.generation_time.get() + elapsed);
+        self.pixels_generated
+            .set(self.pixels_generated.get() + pixels);
+    }
+
+    /// Draws the frame number and stats overlays onto a finished frame and
+    /// writes it to the output, timing how long encoding takes. Frames at the
+    /// start or end of the video are faded first.
+    fn write_frame(
+        &mut self,
+        frame_num: u32,
+        image: &mut [u8],
+        frame_time: Duration,
+    ) -> Result<(), ApplicationRunError> {
+        // only the frames between the ends of the path are played again in
+        // reverse, and they are kept before the overlays are drawn so the
+        // overlays can show the frames they are written as
+        if let Some(buffer) = &mut self.boomerang_buffer {
+            if frame_num > 0 && frame_num < self.path_frames - 1 {
+                buffer.push((Box::from(&*image), frame_time));
             }
+        }
+
+        if self.stats_overlay {
+            self.draw_stats_overlay(image, self.frame_width, frame_time);
+        }
 
-            frame_num += 1;
+        if self.frame_number_overlay {
+            self.draw_frame_number_overlay(image, self.frame_width, frame_num);
         }
 
+        let fade = self.fade_factor(frame_num);
+        if fade < 1f64 {
+            raster::fade_image(image, fade);
+        }
+
+        // the title card comes before the first frame of the video
+        self.encode_frame(frame_num + self.title_frames(), image)
+    }
+
+    /// Writes a frame to the output as is, timing how long encoding takes.
+    fn encode_frame(&mut self, output_frame: u32, image: &[u8]) -> Result<(), ApplicationRunError> {
+        let encoding_start = Instant::now();
+        self.media_out.write_frame(output_frame, image)?;
+        self.encoding_time += encoding_start.elapsed();
+        self.frames_written += 1;
+
         Ok(())
     }
 
+    /// Gets the number of frames the title card takes up.
+    fn title_frames(&self) -> u32 {
+        self.title.as_ref().map_or(0, |(_, frames)| *frames)
+    }
+
+    /// Writes a card with centered text on a solid background for a number of
+    /// frames, starting at the given frame of the output.
+    fn write_card(
+        &mut self,
+        text: &str,
+        first_frame: u32,
+        frames: u32,
+    ) -> Result<(), ApplicationRunError> {
+        let color = self.card_color;
+        let mut image = [color.r, color.g, color.b, color.a]
+            .repeat(self.frame_width as usize * self.view.image_height as usize);
+
+        let scale = Scale::uniform(self.card_text_size);
+        let (text_width, text_height) =
+            raster::get_glyph_line_dimensions(&self.font, scale, 0f32, text);
+        raster::draw_glyph_line(
+            &mut image,
+            self.frame_width,
+            self.view.image_height,
+            &self.font,
+            scale,
+            (
+                self.frame_width.saturating_sub(text_width.ceil() as u32) / 2,
+                self.view
+                    .image_height
+                    .saturating_sub(text_height.ceil() as u32)
+                    / 2,
+            ),
+            0f32,
+            text,
+        );
+
+        for output_frame in first_frame..first_frame + frames {
+            self.encode_frame(output_frame, &image)?;
+        }
+
+        Ok(())
+    }
+
+    /// Gets how bright a frame is while fading in or out, from 0 for black to
+    /// 1 for a frame that isn't faded. The first frame of a fade-in and the
+    /// last frame of a fade-out are completely black.
+    fn fade_factor(&self, frame_num: u32) -> f64 {
+        let mut factor = 1f64;
+
+        if frame_num < self.fade_in {
+            factor = factor.min(frame_num as f64 / self.fade_in as f64);
+        }
+
+        let remaining = self.frames.saturating_sub(frame_num + 1);
+        if remaining < self.fade_out {
+            factor = factor.min(remaining as f64 / self.fade_out as f64);
+        }
+
+        factor
+    }
+
+    /// Prints how fast the fractals were generated and the frames written.
+    /// When frames are pipelined, generation overlaps with encoding and other
+    /// frames, so the generation time can be longer than the wall-clock time.
+    fn print_benchmark(&self, wall_time: Duration) {
+        let per_second = |count: f64, time: Duration| {
+            let seconds = time.as_secs_f64();
+            if seconds > 0f64 {
+                count / seconds
+            } else {
+                0f64
+            }
+        };
+
+        let pixels = self.pixels_generated.get();
+        let generation_time = self.generation_time.get();
+        let frames = self.frames_written as f64;
+
+        println!("Frames:           {}", self.frames_written);
+        println!("Pixels generated: {}", pixels);
+        println!(
+            "Wall-clock time:  {:.3} s ({:.2} frames/s, {:.2} Mpix/s)",
+            wall_time.as_secs_f64(),
+            per_second(frames, wall_time),
+            per_second(pixels as f64, wall_time) / 1_000_000f64
+        );
+        println!(
+            "Generation time:  {:.3} s ({:.2} frames/s, {:.2} Mpix/s)",
+            generation_time.as_secs_f64(),
+            per_second(frames, generation_time),
+            per_second(pixels as f64, generation_time) / 1_000_000f64
+        );
+        println!(
+            "Encoding time:    {:.3} s ({:.2} frames/s)",
+            self.encoding_time.as_secs_f64(),
+            per_second(frames, self.encoding_time)
+        );
+    }
+
+    /// Checks the result of generating a fractal, accepting partially
+    /// generated images if partial frames are allowed.
+    fn accept_generation_result(
+        &self,
+        result: Result<(), generator::FractalGenerationError>,
+    ) -> Result<(), ApplicationRunError> {
+        match result {
+            Ok(()) => Ok(()),
+            Err(generator::FractalGenerationError::IncompleteFrame(partial))
+                if self.allow_partial_frames =>
+            {
+                warn!(
+                    "A fractal thread panicked ({}), leaving {} pixels ungenerated",
+                    partial.panic_message, partial.missing_pixels
+                );
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Draws the orbit of a location on a fractal as connected lines, where `c`
+    /// is only used if the fractal is a Julia set.
+    fn draw_orbit_trail(
+        &self,
+        image: &mut [u8],
+        view: &generator::view::View,
+        params: generator::args::FractalParams,
+        loc: Complex<f64>,
+        c: Complex<f64>,
+    ) {
+        let generator = generator::ValueGenerator::new(*view, params, self.coloring.clone(), c);
+        raster::draw_polyline(image, view, &generator.orbit(loc), self.orbit_color);
+    }
+
+    /// Draws the frame number and its timestamp in the bottom-left corner of
+    /// the image, out of the way of the stats overlay and the minimap.
+    fn draw_frame_number_overlay(&self, image: &mut [u8], image_width: u32, frame_num: u32) {
+        let seconds =
+            frame_num as f64 * self.fps.denominator() as f64 / self.fps.numerator() as f64;
+        let text = format!(
+            "frame {}  {:02}:{:02}:{:06.3}",
+            frame_num,
+            (seconds / 3600f64) as u32,
+            (seconds / 60f64) as u32 % 60,
+            seconds % 60f64
+        );
+
+        let scale = Scale::uniform(12f32);
+        let (_, line_height) = raster::get_glyph_line_dimensions(&self.font, scale, 4f32, &text);
+
+        raster::draw_glyph_line(
+            image,
+            image_width,
+            self.view.image_height,
+            &self.font,
+            scale,
+            (
+                0,
+                self.view
+                    .image_height
+                    .saturating_sub(line_height.ceil() as u32),
+            ),
+            4f32,
+            &text,
+        );
+    }
+
+    /// Draws live generation statistics in the top-left corner of the image.
+    /// This is a debugging aid.
+    fn draw_stats_overlay(&self, image: &mut [u8], image_width: u32, frame_time: Duration) {
+        let lines = stats_overlay_lines(
+            frame_time,
+            self.view.image_width as u64 * self.view.image_height as u64,
+            self.num_threads,
+        );
+
+        raster::draw_glyph_block(
+            image,
+            image_width,
+            self.view.image_height,
+            &self.font,
+            Scale::uniform(12f32),
+            (0, 0),
+            4f32,
+            &lines,
+        );
+    }
+
     fn fractal_progress_callback(&self, progress: Vec<f32>) {
-        println!("Fractal Generation Progress:");
-        print!(" ");
-        for f in progress {
-            print!(" {:.2}%", f * 100f32);
+        match self.progress_format {
+            args::ProgressFormat::Human => {
+                let overall = progress.iter().sum::<f32>() / progress.len().max(1) as f32;
+                self.progress_bar
+                    .set_message(&format!("frame {:.0}%", overall * 100f32));
+            }
+            args::ProgressFormat::Json => {
+                let progress: Vec<String> = progress.iter().map(|f| f.to_string()).collect();
+                print_json_line(&format!(
+                    "{{\"fractal_progress\": [{}]}}",
+                    progress.join(", ")
+                ));
+            }
         }
-        println!();
     }
 
     fn video_progress_callback(&self, frame_num: u32) {
-        println!("Generated {} frames out of {}", frame_num, self.frames);
+        // progress only counts the frames in the range being rendered
+        let done = frame_num + 1 - self.frame_range.start;
+        match self.progress_format {
+            args::ProgressFormat::Human => self.progress_bar.set_position(done as u64),
+            args::ProgressFormat::Json => print_json_line(&format!(
+                "{{\"frame\": {}, \"total\": {}}}",
+                done,
+                self.frame_range.len()
+            )),
+        }
     }
 }
 
+/// Opens the sink the frames are written to, chosen from the arguments: a
+/// still image, an image sequence, a video, or nothing at all when
+/// benchmarking.
+fn open_output(
+    args: &args::CmdArgs,
+    frame_width: u32,
+) -> Result<Box<dyn output::FrameSink>, output::MediaOutputCreationError> {
+    let media_out: Box<dyn output::FrameSink> = match &args.c_path {
+        _ if args.benchmark => {
+            info!("Benchmarking, so nothing is written");
+            Box::new(output::NullOutput)
+        }
+        _ if args.raw => {
+            if output::MediaOutput::is_stdout_path(&args.output) {
+                info!("Writing raw frames to stdout");
+            } else {
+                info!("Writing raw frames to {}", args.output.display());
+            }
+            Box::new(output::RawOutput::new(&args.output))
+        }
+        args::CPath::Point(_) => {
+            info!("Writing a still image to {}", args.output.display());
+            Box::new(output::StillImageOutput::new(
+                &args.output,
+                frame_width,
+                args.image_height,
+            ))
+        }
+        _ if output::PngSequenceOutput::is_sequence_path(&args.output) => {
+            info!("Writing an image sequence to {}", args.output.display());
+            Box::new(output::PngSequenceOutput::new(
+                &args.output,
+                frame_width,
+                args.image_height,
+            ))
+        }
+        _ => {
+            if output::MediaOutput::is_stdout_path(&args.output) {
+                info!("Writing a video to stdout");
+            } else {
+                info!("Writing a video to {}", args.output.display());
+            }
+            Box::new(output::MediaOutput::new(
+                &args.output,
+                frame_width,
+                args.image_height,
+                &output::EncoderSettings {
+                    frame_rate: args.fps,
+                    time_base: args.time_base,
+                    quality: args.quality,
+                    threads: args.encode_threads,
+                    codec: args.codec.clone(),
+                    pixel_format: args.pixel_format.clone(),
+                    options: args.encoder_options.clone(),
+                    format: args.format.clone(),
+                },
+            )?)
+        }
+    };
+
+    Ok(media_out)
+}
+
+/// Generates a fractal image like `generate_fractal`, but if `downsample` is
+/// more than 1, generates it that many times as wide and tall and shrinks it
+/// down to fit the image. A partially generated image is still shrunk.
+fn generate_downsampled_fractal<P: Fn(Vec<f32>)>(
+    generator: &generator::ValueGenerator,
+    image: &mut [u8],
+    pool: &generator::FractalThreadPool,
+    cancel: &Arc<AtomicBool>,
+    progress_callback: P,
+    progress_interval: Duration,
+    downsample: u32,
+) -> Result<(), generator::FractalGenerationError> {
+    if downsample <= 1 {
+        return generator::generate_fractal(
+            generator,
+            image,
+            pool,
+            cancel,
+            progress_callback,
+            progress_interval,
+        );
+    }
+
+    let view = *generator.view();
+    let mut large_image = vec![0u8; image.len() * (downsample * downsample) as usize];
+    let result = generator::generate_fractal(
+        &generator.upscaled(downsample),
+        &mut large_image,
+        pool,
+        cancel,
+        progress_callback,
+        progress_interval,
+    );
+
+    raster::downsample(
+        &large_image,
+        view.image_width * downsample,
+        image,
+        view.image_width,
+        view.image_height,
+        downsample,
+    );
+
+    result
+}
+
+/// Gets how many frames can be generated at once without their buffers going
+/// over a memory limit in bytes, or an error if a single frame doesn't fit.
+fn limit_parallel_frames(
+    args: &args::CmdArgs,
+    frame_width: u32,
+    max_memory: u64,
+) -> Result<usize, ApplicationCreationError> {
+    const MEGABYTE: u64 = 1024 * 1024;
+
+    let frame_bytes = frame_width as u64 * args.image_height as u64 * 4;
+
+    // every sample has a value and a flag for whether it was generated, and
+    // downsampling generates a larger image before shrinking it
+    let downsample = args.downsample as u64;
+    let generated_pixels =
+        args.image_width as u64 * args.image_height as u64 * downsample * downsample;
+    let samples = generated_pixels * args.supersample as u64 * args.supersample as u64;
+    let mut generation_bytes = samples * (8 + 1);
+    if downsample > 1 {
+        generation_bytes += generated_pixels * 4;
+    }
+
+    // a buffered boomerang keeps the frames between the ends of the path
+    let boomerang_bytes = if args.boomerang == Some(args::BoomerangMode::Buffer) {
+        frame_bytes * args.path_frames.saturating_sub(2) as u64
+    } else {
+        0
+    };
+
+    let needed = frame_bytes + generation_bytes + boomerang_bytes;
+    if needed > max_memory {
+        return Err(ApplicationCreationError::MemoryLimitExceeded {
+            needed_mb: (needed + MEGABYTE - 1) / MEGABYTE,
+            limit_mb: max_memory / MEGABYTE,
+        });
+    }
+
+    // each frame generated at once has a buffer being generated and another
+    // waiting to be written
+    let frames = ((max_memory - boomerang_bytes) / (generation_bytes + frame_bytes * 2)).max(1);
+    if (frames as usize) < args.parallel_frames {
+        warn!(
+            "Only generating {} frames at once to stay under the memory limit",
+            frames
+        );
+        Ok(frames as usize)
+    } else {
+        Ok(args.parallel_frames)
+    }
+}
+
+/// Gets the parameters of the fractal being generated from the arguments.
+fn fractal_params(args: &args::CmdArgs) -> generator::args::FractalParams {
+    generator::args::FractalParams {
+        fractal_type: args.fractal_type,
+        power: args.power,
+        iterations: args.iterations,
+        smoothing: args.smoothing,
+        escape_radius: args.escape_radius,
+        supersample: args.supersample,
+        period_check: args.period_check,
+        bulb_check: args.bulb_check,
+        precision: args.precision,
+    }
+}
+
+/// Gets the parameters controlling how the fractal is colored from the
+/// arguments.
+fn coloring_params(args: &args::CmdArgs) -> generator::args::ColoringParams {
+    generator::args::ColoringParams {
+        mode: args.coloring,
+        tone_map: args.tone_map,
+        palette: args.palette.clone(),
+        palette_period: args.palette_period,
+        interior_color: args.interior_color,
+        interior_coloring: args.interior_coloring,
+        hue_scale: args.hue_scale,
+        brightness_scale: args.brightness_scale,
+        gamma: args.gamma,
+        trap_center: [args.trap_center.re, args.trap_center.im],
+        trap_radius: args.trap_radius,
+    }
+}
+
+/// Writes a strip the width of the image showing the colors of values from 0
+/// up to the iteration limit, so the coloring can be checked without a render.
+fn write_palette_preview(args: &args::CmdArgs, path: &Path) -> Result<(), image::ImageError> {
+    let width = args.image_width;
+    let iterations = args.iterations as f64;
+    let generator = generator::ValueGenerator::new(
+        generator::view::View::new_uniform(width, PALETTE_PREVIEW_HEIGHT, 1f64),
+        fractal_params(args),
+        coloring_params(args),
+        Complex::<f64>::new(0f64, 0f64),
+    );
+
+    let row: Vec<u8> = (0..width)
+        .flat_map(|x| {
+            let color: generator::RGBAColor =
+                generator.gen_color(x as f64 * iterations / width as f64);
+            vec![color.r, color.g, color.b, color.a]
+        })
+        .collect();
+
+    output::write_image(
+        &path,
+        width,
+        PALETTE_PREVIEW_HEIGHT,
+        &row.repeat(PALETTE_PREVIEW_HEIGHT as usize),
+    )
+}
+
+/// Creates a view of the plane centered on a point, with a plane height of the
+/// width times the aspect if one is given, or square pixels otherwise.
+fn centered_view(
+    image_width: u32,
+    image_height: u32,
+    plane_width: f64,
+    plane_aspect: Option<f64>,
+    center: Complex<f64>,
+) -> generator::view::View {
+    match plane_aspect {
+        Some(plane_aspect) => generator::view::View::new_stretched(
+            image_width,
+            image_height,
+            plane_width,
+            plane_width * plane_aspect,
+            center,
+        ),
+        None => generator::view::View::new_centered(image_width, image_height, plane_width, center),
+    }
+}
+
+/// Gets the number of frames shown over a number of seconds.
+fn duration_frames(seconds: f64, fps: ffmpeg4::Rational) -> u32 {
+    (seconds * fps.numerator() as f64 / fps.denominator() as f64).round() as u32
+}
+
+/// Finds the value of c at each frame.
+fn frame_points(args: &args::CmdArgs) -> Vec<Complex<f64>> {
+    let mut points = path_frame_points(args);
+
+    // a boomerang walks back along the path without repeating either end
+    if args.boomerang.is_some() && points.len() > 2 {
+        let backward: Vec<_> = points[1..points.len() - 1].iter().rev().cloned().collect();
+        points.extend(backward);
+    }
+
+    points
+}
+
+/// Finds the value of c at each frame along the path.
+fn path_frame_points(args: &args::CmdArgs) -> Vec<Complex<f64>> {
+    match &args.c_path {
+        args::CPath::Svg(path) => path_util::path_points(
+            path.as_slice(),
+            args.path_tolerance,
+            args.path_frames,
+            args.seamless_loop,
+        )
+        .into_iter()
+        .map(|p| Complex::<f64>::new(p.x as f64, p.y as f64))
+        .collect(),
+        args::CPath::Function(c_function) => (0..args.path_frames)
+            .map(|frame_num| {
+                // when looping, t stops one frame short of 1 so the last frame leads
+                // back into the first
+                let steps = if args.seamless_loop {
+                    args.path_frames
+                } else {
+                    args.path_frames - 1
+                };
+                let t = if steps > 0 {
+                    frame_num as f64 / steps as f64
+                } else {
+                    0f64
+                };
+                c_function.evaluate(t)
+            })
+            .collect(),
+        args::CPath::Point(c) => vec![*c],
+    }
+}
+
+/// Prints the video encoders available in the linked ffmpeg, for choosing a
+/// `--codec`.
+fn print_video_encoders() {
+    for (name, description) in output::video_encoders() {
+        println!("{:<24} {}", name, description);
+    }
+}
+
+/// Prints the path of c and the frames it is split into without rendering
+/// anything, so a long render can be checked before it is started.
+fn print_dry_run(args: &args::CmdArgs) {
+    /// The number of points printed at each end of the path.
+    const SHOWN_POINTS: usize = 5;
+
+    if let args::CPath::Svg(path) = &args.c_path {
+        println!(
+            "Path length: {}",
+            path_util::approximate_path_length(path.as_slice(), args.path_tolerance)
+        );
+    }
+
+    let points = frame_points(args);
+    println!("Frames: {}", points.len());
+
+    let print_point = |(frame_num, c): (usize, &Complex<f64>)| {
+        println!("  {:>6}: {:.10} + {:.10}i", frame_num, c.re, c.im)
+    };
+    if points.len() <= SHOWN_POINTS * 2 {
+        points.iter().enumerate().for_each(print_point);
+    } else {
+        points
+            .iter()
+            .enumerate()
+            .take(SHOWN_POINTS)
+            .for_each(print_point);
+        println!("  ...");
+        points
+            .iter()
+            .enumerate()
+            .skip(points.len() - SHOWN_POINTS)
+            .for_each(print_point);
+    }
+}
+
+/// A fractal that takes `c` from the plane, kept between frames because only
+/// the overlays drawn on top of it change unless the view does.
+struct BackgroundCache {
+    params: generator::args::FractalParams,
+    cached: Option<(generator::view::View, u32, Box<[u8]>)>,
+}
+
+impl BackgroundCache {
+    fn new(params: generator::args::FractalParams) -> BackgroundCache {
+        BackgroundCache {
+            params,
+            cached: None,
+        }
+    }
+}
+
+/// Writes a line of JSON to stdout, flushing it right away so programs reading
+/// the output see progress as it happens.
+fn print_json_line(line: &str) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    // progress is best-effort, so a closed stdout shouldn't stop generation
+    let _ = writeln!(stdout, "{}", line).and_then(|_| stdout.flush());
+}
+
+/// Builds the lines of text shown by the stats overlay.
+fn stats_overlay_lines(frame_time: Duration, pixels: u64, num_threads: usize) -> Vec<String> {
+    let seconds = frame_time.as_secs_f64();
+    let megapixels_per_second = if seconds > 0f64 {
+        pixels as f64 / seconds / 1_000_000f64
+    } else {
+        0f64
+    };
+
+    vec![
+        format!("{:.1} ms/frame", seconds * 1000f64),
+        format!("{:.2} Mpix/s", megapixels_per_second),
+        format!("{} threads", num_threads),
+    ]
+}
+
 #[derive(Debug, Clone)]
 enum ApplicationCreationError {
     MediaOutputCreationError(output::MediaOutputCreationError),
+    /// Even a single frame, along with any frames kept for a buffered
+    /// boomerang, needs more memory than `--max-memory` allows.
+    MemoryLimitExceeded {
+        needed_mb: u64,
+        limit_mb: u64,
+    },
+}
+
+impl Display for ApplicationCreationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplicationCreationError::MediaOutputCreationError(e) => e.fmt(f),
+            ApplicationCreationError::MemoryLimitExceeded {
+                needed_mb,
+                limit_mb,
+            } => write!(
+                f,
+                "rendering needs at least {} MB for its frame buffers, which is more than the --max-memory limit of {} MB",
+                needed_mb, limit_mb
+            ),
+        }
+    }
+}
+
+impl Error for ApplicationCreationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ApplicationCreationError::MediaOutputCreationError(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 impl From<output::MediaOutputCreationError> for ApplicationCreationError {
@@ -236,10 +1720,31 @@ impl From<output::MediaOutputCreationError> for ApplicationCreationError {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 enum ApplicationRunError {
     FractalGenerationError(generator::FractalGenerationError),
     MediaWriteError(output::MediaWriteError),
+    ImageWriteError(image::ImageError),
+}
+
+impl Display for ApplicationRunError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplicationRunError::FractalGenerationError(e) => e.fmt(f),
+            ApplicationRunError::MediaWriteError(e) => e.fmt(f),
+            ApplicationRunError::ImageWriteError(e) => write!(f, "could not write an image: {}", e),
+        }
+    }
+}
+
+impl Error for ApplicationRunError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ApplicationRunError::FractalGenerationError(e) => Some(e),
+            ApplicationRunError::MediaWriteError(e) => Some(e),
+            ApplicationRunError::ImageWriteError(e) => Some(e),
+        }
+    }
 }
 
 impl From<generator::FractalGenerationError> for ApplicationRunError {
@@ -253,3 +1758,38 @@ impl From<output::MediaWriteError> for ApplicationRunError {
         ApplicationRunError::MediaWriteError(e)
     }
 }
+
+impl From<image::ImageError> for ApplicationRunError {
+    fn from(e: image::ImageError) -> Self {
+        ApplicationRunError::ImageWriteError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_descriptions_include_causes_once() {
+        let error = args::CmdArgsLoadError::from_int("power", "x".parse::<u32>().unwrap_err());
+
+        assert_eq!(
+            describe_error(&error),
+            "Unable to parse --power argument: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn stats_overlay_shows_frame_time_throughput_and_threads() {
+        let lines = stats_overlay_lines(Duration::from_millis(250), 2_000_000, 6);
+
+        assert_eq!(lines, vec!["250.0 ms/frame", "8.00 Mpix/s", "6 threads"]);
+    }
+
+    #[test]
+    fn stats_overlay_handles_instant_frames() {
+        let lines = stats_overlay_lines(Duration::from_secs(0), 2_000_000, 1);
+
+        assert_eq!(lines, vec!["0.0 ms/frame", "0.00 Mpix/s", "1 threads"]);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::args::CmdArgs;
+use julia_in_motion::generator;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+/// A description of a render, written next to the output so it can be
+/// reproduced or compared later.
+#[derive(Serialize)]
+struct RenderMetadata<'a> {
+    version: &'static str,
+    image_width: u32,
+    image_height: u32,
+    /// The width of each written frame, which is twice the image width when
+    /// the panes are split.
+    frame_width: u32,
+    raw: bool,
+    plane_width: f64,
+    plane_height: Option<f64>,
+    center: [f64; 2],
+    rotation: f64,
+    zoom: Option<(f64, f64)>,
+    fractal: generator::args::FractalType,
+    precision: generator::args::Precision,
+    power: u32,
+    iterations: u32,
+    iteration_ramp: Option<(u32, u32)>,
+    smoothing: generator::args::Smoothing,
+    escape_radius: f64,
+    supersample: u32,
+    downsample: u32,
+    bit_depth: u32,
+    coloring: generator::args::ColoringMode,
+    tone_map: generator::args::ToneMap,
+    gamma: f64,
+    palette_period: Option<f64>,
+    interior_coloring: generator::args::InteriorColoring,
+    trap_center: [f64; 2],
+    trap_radius: f64,
+    path: &'a str,
+    seamless_loop: bool,
+    fps: String,
+    time_base: String,
+    frames: u32,
+    boomerang: bool,
+    start_frame: u32,
+    end_frame: u32,
+    fade_in: u32,
+    fade_out: u32,
+}
+
+/// Gets the path the metadata for an output is written to, which is the
+/// output's path with `.json` appended.
+fn metadata_path(output: &Path) -> PathBuf {
+    let mut name = output
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    name.push(".json");
+    output.with_file_name(name)
+}
+
+/// Writes a JSON description of the render the arguments describe next to
+/// the output.
+pub fn write_metadata(args: &CmdArgs) -> Result<(), serde_json::Error> {
+    let metadata = RenderMetadata {
+        version: clap::crate_version!(),
+        image_width: args.image_width,
+        image_height: args.image_height,
+        frame_width: if args.split {
+            args.image_width * 2
+        } else {
+            args.image_width
+        },
+        raw: args.raw,
+        plane_width: args.plane_width,
+        plane_height: args.plane_height,
+        center: [args.center.re, args.center.im],
+        rotation: args.rotation,
+        zoom: args.zoom,
+        fractal: args.fractal_type,
+        precision: args.precision,
+        power: args.power,
+        iterations: args.iterations,
+        iteration_ramp: args.iteration_ramp,
+        smoothing: args.smoothing,
+        escape_radius: args.escape_radius,
+        supersample: args.supersample,
+        downsample: args.downsample,
+        bit_depth: args.bit_depth,
+        coloring: args.coloring,
+        tone_map: args.tone_map,
+        gamma: args.gamma,
+        palette_period: args.palette_period,
+        interior_coloring: args.interior_coloring,
+        trap_center: [args.trap_center.re, args.trap_center.im],
+        trap_radius: args.trap_radius,
+        path: &args.c_path_source,
+        seamless_loop: args.seamless_loop,
+        fps: format!("{}/{}", args.fps.numerator(), args.fps.denominator()),
+        time_base: format!(
+            "{}/{}",
+            args.time_base.numerator(),
+            args.time_base.denominator()
+        ),
+        frames: args.frames,
+        boomerang: args.boomerang.is_some(),
+        start_frame: args.frame_range.start,
+        end_frame: args.frame_range.end,
+        fade_in: args.fade_in,
+        fade_out: args.fade_out,
+    };
+
+    let file = File::create(metadata_path(&args.output)).map_err(serde_json::Error::io)?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &metadata)
+}
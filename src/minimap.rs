@@ -0,0 +1,207 @@
+use julia_in_motion::{
+    generator,
+    generator::{
+        args::{ColoringParams, FractalParams, FractalType},
+        view::{ConstrainedValue, View},
+    },
+    raster,
+};
+use num_complex::Complex;
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+
+/// The width of the area of the complex plane covered by the minimap.
+const MINIMAP_PLANE_WIDTH: f64 = 4f64;
+
+/// The fraction of the output image's dimensions taken up by the minimap.
+const MINIMAP_SCALE: u32 = 4;
+
+/// The distance in pixels between the minimap and the edges of the image.
+const MINIMAP_MARGIN: u32 = 8;
+
+/// A small, pre-rendered thumbnail of the full Mandelbrot set that can be
+/// drawn in the corner of a frame to show which area is currently in view.
+pub struct Minimap {
+    view: View,
+    image: Box<[u8]>,
+}
+
+impl Minimap {
+    /// Renders a new minimap sized to fit in the corner of an image with the
+    /// given dimensions, or gets `None` if the image is too small to hold one.
+    pub fn new(
+        image_width: u32,
+        image_height: u32,
+        params: FractalParams,
+        coloring: ColoringParams,
+        pool: &generator::FractalThreadPool,
+        cancel: &Arc<AtomicBool>,
+    ) -> Result<Option<Minimap>, generator::FractalGenerationError> {
+        let (width, height) = (image_width / MINIMAP_SCALE, image_height / MINIMAP_SCALE);
+        if width == 0 || height == 0 {
+            return Ok(None);
+        }
+
+        let view = View::new_uniform(width, height, MINIMAP_PLANE_WIDTH);
+
+        let generator = generator::ValueGenerator::new(
+            view,
+            FractalParams {
+                fractal_type: FractalType::Mandelbrot,
+                power: 2,
+                ..params
+            },
+            coloring,
+            Complex::<f64>::new(0f64, 0f64),
+        );
+
+        // the minimap is tiny so there is no point in reporting its progress
+        let mut image = vec![0u8; view.image_width as usize * view.image_height as usize * 4]
+            .into_boxed_slice();
+        generator::generate_fractal(
+            &generator,
+            &mut image,
+            pool,
+            cancel,
+            |_| {},
+            Duration::from_secs(u64::max_value()),
+        )?;
+
+        Ok(Some(Minimap { view, image }))
+    }
+
+    /// Draws the minimap into the bottom-right corner of the image with a
+    /// rectangle marking the area of the complex plane covered by
+    /// `current_view`.
+    pub fn draw(&self, image: &mut [u8], image_width: u32, image_height: u32, current_view: &View) {
+        let (top_left, bottom_right) = self.marked_area(current_view);
+
+        let mut minimap_image = self.image.clone();
+        raster::draw_constrained_rectangle(
+            &mut minimap_image,
+            self.view.image_width,
+            self.view.image_height,
+            top_left,
+            bottom_right,
+        );
+
+        raster::draw_image(
+            image,
+            image_width,
+            image_height,
+            &minimap_image,
+            self.view.image_width,
+            self.view.image_height,
+            (
+                image_width.saturating_sub(self.view.image_width + MINIMAP_MARGIN),
+                image_height.saturating_sub(self.view.image_height + MINIMAP_MARGIN),
+            ),
+        );
+    }
+
+    /// Gets the corners, in minimap pixels, of the rectangle around the area
+    /// of the complex plane covered by a view. A rotated view is marked by
+    /// the rectangle around all four of its corners.
+    fn marked_area(
+        &self,
+        current_view: &View,
+    ) -> (
+        (ConstrainedValue<u32>, ConstrainedValue<u32>),
+        (ConstrainedValue<u32>, ConstrainedValue<u32>),
+    ) {
+        let (min, max) = view_bounds(current_view);
+        (
+            self.view.get_pixel_coordinates(min),
+            self.view.get_pixel_coordinates(max),
+        )
+    }
+}
+
+/// Gets the smallest and largest real and imaginary parts of the corners of
+/// the area of the complex plane covered by a view.
+fn view_bounds(view: &View) -> (Complex<f64>, Complex<f64>) {
+    let corners = [
+        view.get_plane_coordinates((0, 0)),
+        view.get_plane_coordinates((view.image_width, 0)),
+        view.get_plane_coordinates((0, view.image_height)),
+        view.get_plane_coordinates((view.image_width, view.image_height)),
+    ];
+
+    corners
+        .iter()
+        .skip(1)
+        .fold((corners[0], corners[0]), |(min, max), corner| {
+            (
+                Complex::<f64>::new(min.re.min(corner.re), min.im.min(corner.im)),
+                Complex::<f64>::new(max.re.max(corner.re), max.im.max(corner.im)),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    /// Creates a minimap with a blank image, covering the plane from -2 to 2
+    /// at 32 pixels per unit so the pixel coordinates are exact.
+    fn blank_minimap() -> Minimap {
+        Minimap {
+            view: View::new_uniform(128, 128, MINIMAP_PLANE_WIDTH),
+            image: vec![0u8; 128 * 128 * 4].into_boxed_slice(),
+        }
+    }
+
+    #[test]
+    fn marked_area_matches_view_bounds() {
+        let minimap = blank_minimap();
+        let current_view = View::new_centered(256, 128, 1f64, Complex::<f64>::new(0.5f64, 0.25f64));
+
+        assert_eq!(
+            minimap.marked_area(&current_view),
+            (
+                (
+                    ConstrainedValue::WithinConstraint(64),
+                    ConstrainedValue::WithinConstraint(64)
+                ),
+                (
+                    ConstrainedValue::WithinConstraint(96),
+                    ConstrainedValue::WithinConstraint(80)
+                ),
+            )
+        );
+    }
+
+    #[test]
+    fn view_bounds_cover_rotated_view() {
+        let view =
+            View::new_centered(256, 128, 1f64, Complex::<f64>::new(0f64, 0f64)).rotated(PI / 2f64);
+        let (min, max) = view_bounds(&view);
+
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9f64;
+        assert!(close(min.re, -0.25f64) && close(min.im, -0.5f64), "{}", min);
+        assert!(close(max.re, 0.25f64) && close(max.im, 0.5f64), "{}", max);
+    }
+
+    #[test]
+    fn draw_marks_view() {
+        let minimap = blank_minimap();
+        let current_view = View::new_centered(256, 128, 1f64, Complex::<f64>::new(0.5f64, 0.25f64));
+        let mut image = vec![0u8; 512 * 512 * 4];
+
+        minimap.draw(&mut image, 512, 512, &current_view);
+
+        // the minimap is drawn in the bottom-right corner, inset by the margin
+        let origin = 512 - 128 - MINIMAP_MARGIN as usize;
+        let pixel = |x: usize, y: usize| {
+            let index = ((origin + y) * 512 + origin + x) * 4;
+            image[index..index + 4].to_vec()
+        };
+        assert_eq!(pixel(64, 64), vec![255, 255, 255, 255]);
+        assert_eq!(pixel(96, 80), vec![255, 255, 255, 255]);
+        assert_eq!(pixel(80, 72), vec![0, 0, 0, 0]);
+        assert_eq!(pixel(63, 64), vec![0, 0, 0, 0]);
+    }
+}
@@ -1,9 +1,114 @@
-use ffmpeg4::codec;
-use ffmpeg4_sys::{av_opt_set, AV_OPT_SEARCH_CHILDREN};
-use std::ffi::CString;
+use ffmpeg4::{codec, format};
+use ffmpeg4_sys::{
+    av_codec_is_encoder, av_codec_iterate, av_get_pix_fmt, av_guess_format, av_opt_set,
+    av_opt_set_int, av_pix_fmt_desc_get, AVMediaType, AVPixelFormat, AV_OPT_SEARCH_CHILDREN,
+};
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_void,
+    path::Path,
+    ptr,
+};
+
+/// Looks up a pixel format by its ffmpeg name, like `yuv420p`.
+pub fn pixel_from_name(name: &str) -> Option<format::Pixel> {
+    let name = CString::new(name).ok()?;
+
+    match format::Pixel::from(unsafe { av_get_pix_fmt(name.as_ptr()) }) {
+        format::Pixel::None => None,
+        pixel => Some(pixel),
+    }
+}
+
+/// Checks whether ffmpeg can guess a container format for a file from its
+/// name, which it does by the extension.
+pub fn can_guess_format(path: &Path) -> bool {
+    let file_name = match CString::new(path.to_string_lossy().into_owned()) {
+        Ok(file_name) => file_name,
+        Err(_) => return false,
+    };
+
+    !unsafe { av_guess_format(ptr::null(), file_name.as_ptr(), ptr::null()) }.is_null()
+}
+
+/// Gets the names and descriptions of every video encoder in the linked
+/// ffmpeg, sorted by name.
+pub fn video_encoders() -> Vec<(String, String)> {
+    let mut encoders = vec![];
+    let mut opaque: *mut c_void = ptr::null_mut();
+
+    loop {
+        let codec = unsafe { av_codec_iterate(&mut opaque) };
+        if codec.is_null() {
+            break;
+        }
+
+        let codec = unsafe { &*codec };
+        if codec.type_ != AVMediaType::AVMEDIA_TYPE_VIDEO
+            || unsafe { av_codec_is_encoder(codec) } == 0
+        {
+            continue;
+        }
+
+        let name = unsafe { CStr::from_ptr(codec.name) }
+            .to_string_lossy()
+            .into_owned();
+        let description = if codec.long_name.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(codec.long_name) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        encoders.push((name, description));
+    }
+
+    encoders.sort();
+    encoders
+}
+
+/// Gets the number of pixels that share a single chroma sample horizontally
+/// and vertically in a pixel format, like `(2, 2)` for `yuv420p`.
+pub fn chroma_block_size(pixel: format::Pixel) -> (u32, u32) {
+    let descriptor = unsafe { av_pix_fmt_desc_get(AVPixelFormat::from(pixel)) };
+
+    if descriptor.is_null() {
+        (1, 1)
+    } else {
+        let descriptor = unsafe { &*descriptor };
+        (1 << descriptor.log2_chroma_w, 1 << descriptor.log2_chroma_h)
+    }
+}
+
+/// Checks whether an encoder runs on dedicated hardware, like `h264_nvenc`.
+/// These take ordinary frames from memory but need a device to open.
+pub fn is_hardware_encoder(name: &str) -> bool {
+    [
+        "_nvenc",
+        "_qsv",
+        "_amf",
+        "_vaapi",
+        "_videotoolbox",
+        "_v4l2m2m",
+    ]
+    .iter()
+    .any(|suffix| name.ends_with(suffix))
+}
+
+/// Gets the name of the option an encoder uses for constant quality encoding.
+/// Most software encoders call this `crf`, while NVENC calls it `cq`.
+pub fn constant_quality_option(name: &str) -> &'static str {
+    if name.ends_with("_nvenc") {
+        "cq"
+    } else {
+        "crf"
+    }
+}
 
 pub trait OptionSettable {
     fn opt_set_str(&mut self, name: &str, value: &str) -> Result<(), ffmpeg4::Error>;
+
+    fn opt_set_int(&mut self, name: &str, value: i64) -> Result<(), ffmpeg4::Error>;
 }
 
 impl OptionSettable for codec::Context {
@@ -23,4 +128,33 @@ impl OptionSettable for codec::Context {
             e => Err(ffmpeg4::Error::from(e)),
         }
     }
+
+    fn opt_set_int(&mut self, name: &str, value: i64) -> Result<(), ffmpeg4::Error> {
+        let name = CString::new(name).unwrap();
+
+        match unsafe {
+            av_opt_set_int(
+                self.as_mut_ptr() as *mut _,
+                name.as_ptr(),
+                value,
+                AV_OPT_SEARCH_CHILDREN,
+            )
+        } {
+            0 => Ok(()),
+            e => Err(ffmpeg4::Error::from(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chroma_block_size_follows_subsampling() {
+        assert_eq!(chroma_block_size(format::Pixel::YUV420P), (2, 2));
+        assert_eq!(chroma_block_size(format::Pixel::YUV422P), (2, 1));
+        assert_eq!(chroma_block_size(format::Pixel::YUV444P), (1, 1));
+        assert_eq!(chroma_block_size(format::Pixel::RGBA), (1, 1));
+    }
 }
@@ -1,29 +1,229 @@
 use extra::OptionSettable;
-use ffmpeg4::{codec, encoder, format, frame, media, software, Packet, Rational};
-use std::{option::NoneError, path::Path};
+use ffmpeg4::{codec, encoder, format, frame, media, software, Packet, Rational, Rescale};
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+    option::NoneError,
+    path::Path,
+};
+
+pub use extra::video_encoders;
+pub use png::{PngSequenceOutput, StillImageOutput};
+pub use raw::RawOutput;
 
 mod extra;
+mod png;
+mod raw;
+
+/// How the quality of an encoded video is controlled.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum VideoQuality {
+    /// A constant rate factor, where lower values give higher quality.
+    Crf(u32),
+    /// A fixed bitrate in bits per second.
+    Bitrate(usize),
+}
+
+/// Settings controlling how a video is encoded.
+#[derive(Debug, Clone)]
+pub struct EncoderSettings {
+    pub frame_rate: Rational,
+    pub time_base: Rational,
+    pub quality: VideoQuality,
+    /// The number of threads the encoder uses, or `None` to let ffmpeg decide.
+    pub threads: Option<u32>,
+    /// The name of the encoder to use, or `None` to use the output format's
+    /// default.
+    pub codec: Option<String>,
+    /// The name of the pixel format to encode with, or `None` for `yuv420p`.
+    pub pixel_format: Option<String>,
+    /// Extra private options for the encoder, like `preset` or `rc` for
+    /// hardware encoders, applied in order before it is opened.
+    pub options: Vec<(String, String)>,
+    /// The name of the container format, like `matroska`, or `None` to guess
+    /// it from the output path's extension. Required when writing to stdout.
+    pub format: Option<String>,
+}
+
+/// Something that the frames of a video can be written to.
+pub trait FrameSink {
+    /// Prepares the output for frames to be written.
+    fn start(&mut self) -> Result<(), MediaWriteError>;
+
+    /// Writes a single RGBA frame.
+    fn write_frame(&mut self, frame_num: u32, image: &[u8]) -> Result<(), MediaWriteError>;
+
+    /// Finishes writing the output after the last frame.
+    fn finish(&mut self) -> Result<(), MediaWriteError>;
+}
+
+/// Writes a single RGBA image to a file. The image format is determined by the
+/// file's extension.
+pub fn write_image<P: AsRef<Path>>(
+    path: &P,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> Result<(), image::ImageError> {
+    image::save_buffer(path, data, width, height, image::ColorType::Rgba8)
+}
+
+/// Writes a single RGBA image with 16 bits per channel to a file. Only some
+/// image formats, like PNG and TIFF, can hold 16 bits per channel.
+pub fn write_image_16<P: AsRef<Path>>(
+    path: &P,
+    width: u32,
+    height: u32,
+    data: &[u16],
+) -> Result<(), image::ImageError> {
+    image::ImageBuffer::<image::Rgba<u16>, _>::from_raw(width, height, data)
+        .expect("the image data doesn't match its dimensions")
+        .save(path)
+}
+
+/// Lets the encoder do its own threading independently of the fractal
+/// generator, with the given number of threads or as many as ffmpeg decides.
+fn set_encoder_threads(
+    encoder: &mut encoder::video::Video,
+    threads: Option<u32>,
+) -> Result<(), ffmpeg4::Error> {
+    if let Some(threads) = threads {
+        encoder.opt_set_int("threads", threads as i64)?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the image dimensions cover whole chroma blocks, as subsampled
+/// formats like yuv420p can't represent a partial one.
+fn check_dimensions(
+    width: u32,
+    height: u32,
+    pixel_format: format::Pixel,
+) -> Result<(), MediaOutputCreationError> {
+    let (block_width, block_height) = extra::chroma_block_size(pixel_format);
+    if width % block_width != 0 || height % block_height != 0 {
+        return Err(MediaOutputCreationError::UnsupportedDimensions {
+            width,
+            height,
+            block_width,
+            block_height,
+        });
+    }
+
+    Ok(())
+}
+
+/// Gets the timestamp of a frame in units of the time base, as frames are
+/// numbered at the frame rate rather than in time base units.
+fn frame_pts(frame_num: u32, frame_rate: Rational, time_base: Rational) -> i64 {
+    (frame_num as i64).rescale(frame_rate.invert(), time_base)
+}
+
+/// Discards every frame, so generation can be measured without any file I/O.
+pub struct NullOutput;
+
+impl FrameSink for NullOutput {
+    fn start(&mut self) -> Result<(), MediaWriteError> {
+        Ok(())
+    }
+
+    fn write_frame(&mut self, _frame_num: u32, _image: &[u8]) -> Result<(), MediaWriteError> {
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), MediaWriteError> {
+        Ok(())
+    }
+}
 
 pub struct MediaOutput {
     format_context: format::context::Output,
     encoder: codec::encoder::Video,
     converter: software::scaling::Context,
     in_time_base: Rational,
+    frame_rate: Rational,
+    frame: frame::Video,
     converted: frame::Video,
     encoded: Packet,
 }
 
 impl MediaOutput {
-    pub fn new<P: AsRef<Path>, R: Into<Rational>>(
+    /// Checks whether an output path means the video should be written to
+    /// stdout, which is the case for `-`.
+    pub fn is_stdout_path(path: &Path) -> bool {
+        path == Path::new("-")
+    }
+
+    pub fn new<P: AsRef<Path>>(
         path: &P,
         width: u32,
         height: u32,
-        time_base: R,
+        settings: &EncoderSettings,
     ) -> Result<MediaOutput, MediaOutputCreationError> {
-        let time_base = time_base.into();
-        let mut format_context = format::output(path)?;
-        let codec =
-            encoder::find(format_context.format().codec(path, media::Type::Video))?.video()?;
+        let time_base = settings.time_base;
+        let mut format_context = if MediaOutput::is_stdout_path(path.as_ref()) {
+            // ffmpeg's pipe protocol writes to stdout, but it can't guess a container from a name
+            let name = settings
+                .format
+                .as_ref()
+                .ok_or(MediaOutputCreationError::MissingFormat)?;
+            format::output_as(&"pipe:1", name)?
+        } else {
+            match &settings.format {
+                Some(name) => format::output_as(path, name)?,
+                None => {
+                    // ffmpeg's own error for an unknown extension doesn't say what was wrong
+                    if !extra::can_guess_format(path.as_ref()) {
+                        return Err(MediaOutputCreationError::UnknownContainer(
+                            path.as_ref()
+                                .extension()
+                                .map(|extension| extension.to_string_lossy().into_owned())
+                                .unwrap_or_default(),
+                        ));
+                    }
+                    format::output(path)?
+                }
+            }
+        };
+        let codec = match &settings.codec {
+            Some(name) => encoder::find_by_name(name)
+                .ok_or_else(|| MediaOutputCreationError::UnknownCodec(name.clone()))?,
+            None => encoder::find(format_context.format().codec(path, media::Type::Video))
+                .ok_or_else(|| {
+                    MediaOutputCreationError::NoDefaultCodec(
+                        format_context.format().name().to_owned(),
+                    )
+                })?,
+        }
+        .video()?;
+
+        let pixel_format = match &settings.pixel_format {
+            Some(name) => extra::pixel_from_name(name)
+                .ok_or_else(|| MediaOutputCreationError::UnknownPixelFormat(name.clone()))?,
+            None => format::Pixel::YUV420P,
+        };
+
+        // make sure the encoder can actually take the chosen pixel format
+        if let Some(mut formats) = codec.formats() {
+            if !formats.any(|format| format == pixel_format) {
+                return Err(MediaOutputCreationError::UnsupportedPixelFormat(
+                    settings
+                        .pixel_format
+                        .clone()
+                        .unwrap_or_else(|| "yuv420p".to_owned()),
+                ));
+            }
+        }
+
+        log::debug!(
+            "Encoding with {} in the {:?} pixel format",
+            codec.name(),
+            pixel_format
+        );
+
+        check_dimensions(width, height, pixel_format)?;
 
         let global_header = format_context
             .format()
@@ -37,44 +237,78 @@ impl MediaOutput {
             encoder.set_flags(codec::Flags::GLOBAL_HEADER);
         }
 
-        encoder.set_frame_rate(Some((30, 1)));
-        encoder.set_format(format::Pixel::YUV420P);
-        encoder.set_bit_rate(0);
-        encoder.opt_set_str("crf", "30")?;
+        encoder.set_frame_rate(Some(settings.frame_rate));
+        encoder.set_format(pixel_format);
+        match settings.quality {
+            VideoQuality::Crf(crf) => {
+                encoder.set_bit_rate(0);
+                encoder.opt_set_str(
+                    extra::constant_quality_option(codec.name()),
+                    &crf.to_string(),
+                )?;
+            }
+            VideoQuality::Bitrate(bit_rate) => encoder.set_bit_rate(bit_rate),
+        }
         encoder.set_width(width);
         encoder.set_height(height);
         encoder.set_time_base(time_base);
         output.set_time_base(time_base);
 
-        let encoder = encoder.open_as(codec)?;
+        set_encoder_threads(&mut encoder, settings.threads)?;
+
+        for (name, value) in &settings.options {
+            encoder.opt_set_str(name, value).map_err(|error| {
+                MediaOutputCreationError::InvalidEncoderOption {
+                    name: name.clone(),
+                    value: value.clone(),
+                    error,
+                }
+            })?;
+        }
+
+        // hardware encoders can be built into ffmpeg but still fail to open without a device
+        let codec_name = codec.name().to_owned();
+        let encoder = encoder.open_as(codec).map_err(|error| {
+            MediaOutputCreationError::EncoderUnavailable {
+                codec: codec_name,
+                error,
+            }
+        })?;
 
         output.set_parameters(&encoder);
 
-        let converter =
-            software::converter((width, height), format::Pixel::RGBA, format::Pixel::YUV420P)?;
+        let converter = software::converter((width, height), format::Pixel::RGBA, pixel_format)?;
 
         Ok(MediaOutput {
             format_context,
             encoder,
             converter,
             in_time_base: time_base,
+            frame_rate: settings.frame_rate,
+            frame: frame::Video::new(format::Pixel::RGBA, width, height),
             converted: frame::Video::empty(),
             encoded: Packet::empty(),
         })
     }
+}
 
-    pub fn start(&mut self) -> Result<(), MediaWriteError> {
+impl FrameSink for MediaOutput {
+    fn start(&mut self) -> Result<(), MediaWriteError> {
         self.format_context.write_header()?;
 
         Ok(())
     }
 
-    pub fn write_frame(
-        &mut self,
-        frame: &frame::Video,
-    ) -> Result<MediaWriteResult, MediaWriteError> {
-        self.converter.run(frame, &mut self.converted)?;
-        self.converted.set_pts(frame.pts());
+    fn write_frame(&mut self, frame_num: u32, image: &[u8]) -> Result<(), MediaWriteError> {
+        self.frame.data_mut(0).copy_from_slice(image);
+        self.frame.set_pts(Some(frame_pts(
+            frame_num,
+            self.frame_rate,
+            self.in_time_base,
+        )));
+
+        self.converter.run(&self.frame, &mut self.converted)?;
+        self.converted.set_pts(self.frame.pts());
 
         if self.encoder.encode(&self.converted, &mut self.encoded)? {
             self.encoded.set_stream(0);
@@ -83,16 +317,12 @@ impl MediaOutput {
                 self.format_context.stream(0)?.time_base(),
             );
             self.encoded.write_interleaved(&mut self.format_context)?;
-
-            Ok(MediaWriteResult::PacketWritten)
-        } else {
-            Ok(MediaWriteResult::NoPacketWritten)
         }
-    }
 
-    pub fn finish(&mut self) -> Result<MediaWriteResult, MediaWriteError> {
-        let mut res = MediaWriteResult::NoPacketWritten;
+        Ok(())
+    }
 
+    fn finish(&mut self) -> Result<(), MediaWriteError> {
         // sometimes there are a bunch of unwritten frames
         while self.encoder.flush(&mut self.encoded)? {
             self.encoded.set_stream(0);
@@ -101,13 +331,11 @@ impl MediaOutput {
                 self.format_context.stream(0)?.time_base(),
             );
             self.encoded.write_interleaved(&mut self.format_context)?;
-
-            res = MediaWriteResult::PacketWritten;
         }
 
         self.format_context.write_trailer()?;
 
-        Ok(res)
+        Ok(())
     }
 }
 
@@ -115,6 +343,125 @@ impl MediaOutput {
 pub enum MediaOutputCreationError {
     FfmpegError(ffmpeg4::Error),
     MissingComponentError,
+    /// The output is stdout but no container format was given.
+    MissingFormat,
+    /// ffmpeg can't guess a container format from the output's extension,
+    /// which is empty if it has none.
+    UnknownContainer(String),
+    /// The container format has no default video encoder available in the
+    /// linked ffmpeg.
+    NoDefaultCodec(String),
+    /// No encoder with the given name is available in the linked ffmpeg.
+    UnknownCodec(String),
+    /// ffmpeg doesn't know of a pixel format with the given name.
+    UnknownPixelFormat(String),
+    /// The encoder doesn't support the given pixel format.
+    UnsupportedPixelFormat(String),
+    /// The encoder rejected an option set with `--encoder-option`.
+    InvalidEncoderOption {
+        name: String,
+        value: String,
+        error: ffmpeg4::Error,
+    },
+    /// The encoder is built into ffmpeg but couldn't be opened, usually
+    /// because a hardware encoder's device or driver is missing.
+    EncoderUnavailable {
+        codec: String,
+        error: ffmpeg4::Error,
+    },
+    /// The image dimensions aren't a multiple of the pixel format's chroma
+    /// block size, like odd dimensions with `yuv420p`.
+    UnsupportedDimensions {
+        width: u32,
+        height: u32,
+        block_width: u32,
+        block_height: u32,
+    },
+}
+
+impl Display for MediaOutputCreationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MediaOutputCreationError::FfmpegError(e) => {
+                write!(f, "could not create the video output: {}", e)
+            }
+            MediaOutputCreationError::MissingComponentError => {
+                write!(f, "the video output is missing a required ffmpeg component")
+            }
+            MediaOutputCreationError::MissingFormat => write!(
+                f,
+                "a container format must be chosen with --format when writing to stdout"
+            ),
+            MediaOutputCreationError::UnknownContainer(extension) => {
+                if extension.is_empty() {
+                    write!(f, "the output has no extension to guess a container format from")?;
+                } else {
+                    write!(
+                        f,
+                        "no container format is known for the .{} extension",
+                        extension
+                    )?;
+                }
+                write!(
+                    f,
+                    "; use a supported extension like .mp4, .mkv, or .webm, or choose a format with --format"
+                )
+            }
+            MediaOutputCreationError::NoDefaultCodec(format) => write!(
+                f,
+                "the {} container has no video encoder available in the linked ffmpeg; choose one with --codec or use a container like mp4, mkv, or webm",
+                format
+            ),
+            MediaOutputCreationError::UnknownCodec(name) => write!(
+                f,
+                "unknown codec: {} (the linked ffmpeg may not have been built with it)",
+                name
+            ),
+            MediaOutputCreationError::UnknownPixelFormat(name) => {
+                write!(f, "unknown pixel format: {}", name)
+            }
+            MediaOutputCreationError::UnsupportedPixelFormat(name) => {
+                write!(f, "the codec does not support the pixel format {}", name)
+            }
+            MediaOutputCreationError::InvalidEncoderOption { name, value, error } => write!(
+                f,
+                "the encoder rejected the option {}={}: {}",
+                name, value, error
+            ),
+            MediaOutputCreationError::EncoderUnavailable { codec, error } => {
+                if extra::is_hardware_encoder(codec) {
+                    write!(
+                        f,
+                        "could not open the {} encoder: {} (is a supported GPU and driver available?)",
+                        codec, error
+                    )
+                } else {
+                    write!(f, "could not open the {} encoder: {}", codec, error)
+                }
+            }
+            MediaOutputCreationError::UnsupportedDimensions {
+                width,
+                height,
+                block_width,
+                block_height,
+            } => write!(
+                f,
+                "the pixel format requires the width to be a multiple of {} and the height to be a multiple of {}, but the image is {}x{}",
+                block_width, block_height, width, height
+            ),
+        }
+    }
+}
+
+impl Error for MediaOutputCreationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MediaOutputCreationError::FfmpegError(e) => Some(e),
+            MediaOutputCreationError::InvalidEncoderOption { error, .. } => Some(error),
+            MediaOutputCreationError::EncoderUnavailable { error, .. } => Some(error),
+            _ => None,
+        }
+    }
 }
 
 impl From<ffmpeg4::Error> for MediaOutputCreationError {
@@ -129,10 +476,36 @@ impl From<NoneError> for MediaOutputCreationError {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub enum MediaWriteError {
     FfmpegError(ffmpeg4::Error),
     MissingComponentError,
+    ImageError(image::ImageError),
+    IOError(io::Error),
+}
+
+impl Display for MediaWriteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MediaWriteError::FfmpegError(e) => write!(f, "could not encode the video: {}", e),
+            MediaWriteError::MissingComponentError => {
+                write!(f, "the video output is missing a required ffmpeg component")
+            }
+            MediaWriteError::ImageError(e) => write!(f, "could not write an image: {}", e),
+            MediaWriteError::IOError(e) => write!(f, "could not write the output: {}", e),
+        }
+    }
+}
+
+impl Error for MediaWriteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MediaWriteError::FfmpegError(e) => Some(e),
+            MediaWriteError::MissingComponentError => None,
+            MediaWriteError::ImageError(e) => Some(e),
+            MediaWriteError::IOError(e) => Some(e),
+        }
+    }
 }
 
 impl From<ffmpeg4::Error> for MediaWriteError {
@@ -147,8 +520,74 @@ impl From<NoneError> for MediaWriteError {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub enum MediaWriteResult {
-    PacketWritten,
-    NoPacketWritten,
+impl From<image::ImageError> for MediaWriteError {
+    fn from(e: image::ImageError) -> Self {
+        MediaWriteError::ImageError(e)
+    }
+}
+
+impl From<io::Error> for MediaWriteError {
+    fn from(e: io::Error) -> Self {
+        MediaWriteError::IOError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_pts_follows_time_base() {
+        let frame_rate = Rational::new(30, 1);
+
+        assert_eq!(frame_pts(0, frame_rate, Rational::new(1, 30)), 0);
+        assert_eq!(frame_pts(7, frame_rate, Rational::new(1, 30)), 7);
+        assert_eq!(frame_pts(1, frame_rate, Rational::new(1, 90000)), 3000);
+        assert_eq!(frame_pts(30, frame_rate, Rational::new(1, 90000)), 90000);
+        assert_eq!(
+            frame_pts(30, Rational::new(30000, 1001), Rational::new(1, 90000)),
+            90090
+        );
+    }
+
+    #[test]
+    fn odd_dimensions_are_rejected_for_subsampled_formats() {
+        assert!(matches!(
+            check_dimensions(101, 101, format::Pixel::YUV420P),
+            Err(MediaOutputCreationError::UnsupportedDimensions {
+                width: 101,
+                height: 101,
+                block_width: 2,
+                block_height: 2,
+            })
+        ));
+        assert!(check_dimensions(100, 100, format::Pixel::YUV420P).is_ok());
+        assert!(check_dimensions(101, 101, format::Pixel::YUV444P).is_ok());
+    }
+
+    fn new_encoder() -> encoder::video::Video {
+        codec::context::Context::new()
+            .encoder()
+            .video()
+            .expect("a new codec context should become a video encoder")
+    }
+
+    fn thread_count(encoder: &encoder::video::Video) -> i32 {
+        unsafe { (*encoder.as_ptr()).thread_count }
+    }
+
+    #[test]
+    fn encoder_threads_are_set() {
+        let mut encoder = new_encoder();
+        set_encoder_threads(&mut encoder, Some(3)).unwrap();
+        assert_eq!(thread_count(&encoder), 3);
+    }
+
+    #[test]
+    fn encoder_threads_default_without_setting() {
+        let mut encoder = new_encoder();
+        let default = thread_count(&encoder);
+        set_encoder_threads(&mut encoder, None).unwrap();
+        assert_eq!(thread_count(&encoder), default);
+    }
 }
@@ -0,0 +1,123 @@
+use super::{write_image, FrameSink, MediaWriteError};
+use regex::Regex;
+use std::{
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+};
+
+lazy_static::lazy_static! {
+    static ref FRAME_NUMBER_REGEX: Regex = Regex::new(r"%0?(\d*)d").unwrap();
+}
+
+/// The file name pattern used when a sequence is written to a directory.
+const DEFAULT_FRAME_PATTERN: &str = "frame_%05d.png";
+
+/// Writes each frame as a separate image file, named after a pattern like
+/// `frame_%05d.png`.
+pub struct PngSequenceOutput {
+    directory: PathBuf,
+    prefix: String,
+    digits: usize,
+    suffix: String,
+    width: u32,
+    height: u32,
+}
+
+/// Writes a single frame to an image file.
+pub struct StillImageOutput {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+}
+
+impl PngSequenceOutput {
+    /// Checks whether an output path describes an image sequence rather than a
+    /// video file. This is the case if the path is a directory or its file name
+    /// contains a frame number pattern like `%05d`.
+    pub fn is_sequence_path(path: &Path) -> bool {
+        path.is_dir()
+            || path.to_string_lossy().ends_with(std::path::is_separator)
+            || path.file_name().map_or(false, |name| {
+                FRAME_NUMBER_REGEX.is_match(&name.to_string_lossy())
+            })
+    }
+
+    pub fn new(path: &Path, width: u32, height: u32) -> PngSequenceOutput {
+        let (directory, pattern) = match path.file_name() {
+            Some(name) if FRAME_NUMBER_REGEX.is_match(&name.to_string_lossy()) => (
+                path.parent().map(Path::to_path_buf).unwrap_or_default(),
+                name.to_string_lossy().into_owned(),
+            ),
+            _ => (path.to_path_buf(), DEFAULT_FRAME_PATTERN.to_owned()),
+        };
+
+        let captures = FRAME_NUMBER_REGEX.captures(&pattern).unwrap();
+        let number = captures.get(0).unwrap();
+        let digits = captures[1].parse::<usize>().unwrap_or(0);
+
+        PngSequenceOutput {
+            directory,
+            prefix: pattern[..number.start()].to_owned(),
+            digits,
+            suffix: pattern[number.end()..].to_owned(),
+            width,
+            height,
+        }
+    }
+
+    fn frame_path(&self, frame_num: u32) -> PathBuf {
+        self.directory.join(format!(
+            "{}{:0digits$}{}",
+            self.prefix,
+            frame_num,
+            self.suffix,
+            digits = self.digits
+        ))
+    }
+}
+
+impl FrameSink for PngSequenceOutput {
+    fn start(&mut self) -> Result<(), MediaWriteError> {
+        if !self.directory.as_os_str().is_empty() && !self.directory.exists() {
+            create_dir_all(&self.directory)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame_num: u32, image: &[u8]) -> Result<(), MediaWriteError> {
+        write_image(&self.frame_path(frame_num), self.width, self.height, image)?;
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), MediaWriteError> {
+        Ok(())
+    }
+}
+
+impl StillImageOutput {
+    pub fn new(path: &Path, width: u32, height: u32) -> StillImageOutput {
+        StillImageOutput {
+            path: path.to_path_buf(),
+            width,
+            height,
+        }
+    }
+}
+
+impl FrameSink for StillImageOutput {
+    fn start(&mut self) -> Result<(), MediaWriteError> {
+        Ok(())
+    }
+
+    fn write_frame(&mut self, _frame_num: u32, image: &[u8]) -> Result<(), MediaWriteError> {
+        write_image(&self.path, self.width, self.height, image)?;
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), MediaWriteError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use super::{FrameSink, MediaOutput, MediaWriteError};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Writes the RGBA bytes of each frame back to back without any header or
+/// encoding, to a file or to stdout, for other programs to process. The
+/// dimensions and frame rate aren't stored, so they have to be known by
+/// whatever reads the frames.
+pub struct RawOutput {
+    path: PathBuf,
+    writer: Option<BufWriter<Box<dyn Write>>>,
+}
+
+impl RawOutput {
+    /// Checks whether an output path should have raw frames written to it,
+    /// which is the case if its extension is `.raw`.
+    pub fn is_raw_path(path: &Path) -> bool {
+        path.extension().map_or(false, |extension| {
+            extension.to_string_lossy().eq_ignore_ascii_case("raw")
+        })
+    }
+
+    pub fn new(path: &Path) -> RawOutput {
+        RawOutput {
+            path: path.to_path_buf(),
+            writer: None,
+        }
+    }
+}
+
+impl FrameSink for RawOutput {
+    fn start(&mut self) -> Result<(), MediaWriteError> {
+        let writer: Box<dyn Write> = if MediaOutput::is_stdout_path(&self.path) {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(&self.path)?)
+        };
+        self.writer = Some(BufWriter::new(writer));
+
+        Ok(())
+    }
+
+    fn write_frame(&mut self, _frame_num: u32, image: &[u8]) -> Result<(), MediaWriteError> {
+        self.writer
+            .as_mut()
+            .expect("raw output written to before it was started")
+            .write_all(image)?;
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), MediaWriteError> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,19 +1,103 @@
-use lyon_algorithms::walk::{walk_along_path, RegularPattern};
-use lyon_path::{iterator::PathIterator, math::Point, Event, PathSlice};
+use lyon_path::{
+    geom::{CubicBezierSegment, QuadraticBezierSegment},
+    iterator::PathIterator,
+    math::{point, Point, Vector},
+    Event, Path, PathSlice,
+};
+use std::f32::consts::PI;
 
-/// Approximates the length of a path given a tolerance.
+/// How far along a circle each cubic curve's control points are placed when a
+/// quarter circle is approximated.
+const QUARTER_CIRCLE_KAPPA: f32 = 0.552_284_8;
+
+/// The number of line segments used for each turn of a spiral.
+const SPIRAL_SEGMENTS_PER_TURN: u32 = 128;
+
+/// Builds a closed circular path, starting on the right-hand side of the
+/// circle and going counter-clockwise.
+pub fn circle_path(center: Point, radius: f32) -> Path {
+    let offset = |x: f32, y: f32| center + Vector::new(x * radius, y * radius);
+    let k = QUARTER_CIRCLE_KAPPA;
+
+    let mut builder = Path::builder();
+    builder.move_to(offset(1f32, 0f32));
+    builder.cubic_bezier_to(offset(1f32, k), offset(k, 1f32), offset(0f32, 1f32));
+    builder.cubic_bezier_to(offset(-k, 1f32), offset(-1f32, k), offset(-1f32, 0f32));
+    builder.cubic_bezier_to(offset(-1f32, -k), offset(-k, -1f32), offset(0f32, -1f32));
+    builder.cubic_bezier_to(offset(k, -1f32), offset(1f32, -k), offset(1f32, 0f32));
+    builder.close();
+
+    builder.build()
+}
+
+/// Builds a path of straight lines between each of the given points.
+pub fn polyline_path(points: &[Point]) -> Path {
+    let mut builder = Path::builder();
+    for (index, &at) in points.iter().enumerate() {
+        if index == 0 {
+            builder.move_to(at);
+        } else {
+            builder.line_to(at);
+        }
+    }
+
+    builder.build()
+}
+
+/// Builds a spiral path whose radius changes evenly from `start_radius` to
+/// `end_radius` over the given number of turns.
+pub fn spiral_path(center: Point, start_radius: f32, end_radius: f32, turns: f32) -> Path {
+    let segments = ((turns.abs() * SPIRAL_SEGMENTS_PER_TURN as f32).ceil() as u32).max(1);
+
+    let mut builder = Path::builder();
+    for segment in 0..=segments {
+        let fraction = segment as f32 / segments as f32;
+        let angle = fraction * turns * 2f32 * PI;
+        let radius = start_radius + (end_radius - start_radius) * fraction;
+        let at = point(
+            center.x + radius * angle.cos(),
+            center.y + radius * angle.sin(),
+        );
+
+        if segment == 0 {
+            builder.move_to(at);
+        } else {
+            builder.line_to(at);
+        }
+    }
+
+    builder.build()
+}
+
+/// Approximates the length of a path given a tolerance. Curves are measured
+/// directly rather than relying on the path being flattened first.
 pub fn approximate_path_length(path: PathSlice, tolerance: f32) -> f32 {
     // More or less copied from https://github.com/nical/lyon/blob/cb23ba4a527b2f246ec54a0cfde01f062f2b5159/path/src/iterator.rs#L706
 
     let mut length = 0f32;
-    for event in path.iter().flattened(tolerance) {
+    for event in path.iter() {
         match event {
             Event::Begin { .. } => {}
             Event::Line { from, to } => {
                 length += (to - from).length();
             }
-            Event::Quadratic { .. } => {}
-            Event::Cubic { .. } => {}
+            Event::Quadratic { from, ctrl, to } => {
+                length += QuadraticBezierSegment { from, ctrl, to }.approximate_length(tolerance);
+            }
+            Event::Cubic {
+                from,
+                ctrl1,
+                ctrl2,
+                to,
+            } => {
+                length += CubicBezierSegment {
+                    from,
+                    ctrl1,
+                    ctrl2,
+                    to,
+                }
+                .approximate_length(tolerance);
+            }
             Event::End { last, first, close } => {
                 if close {
                     length += (first - last).length();
@@ -25,20 +109,163 @@ pub fn approximate_path_length(path: PathSlice, tolerance: f32) -> f32 {
     length
 }
 
-/// Walks along a path and returns a vector of points at regular intervals.
-pub fn path_points(path: PathSlice, curve_tolerance: f32, interval: f32) -> Vec<Point> {
-    let mut points = vec![];
+/// Checks whether a path ends by closing itself, like a circle, so that it can
+/// be looped.
+pub fn is_closed(path: PathSlice) -> bool {
+    let mut closed = false;
+    for event in path.iter() {
+        if let Event::End { close, .. } = event {
+            closed = close;
+        }
+    }
+
+    closed
+}
 
-    let mut pattern = RegularPattern {
-        callback: &mut |point: Point, _, _| {
-            points.push(point);
+/// Walks along a path and returns exactly `count` points spread evenly along
+/// it, with the first and last points at the ends of the path. When `seamless`
+/// is set, the last point stops one step short of the end so that a closed
+/// path loops without repeating its starting point.
+pub fn path_points(
+    path: PathSlice,
+    curve_tolerance: f32,
+    count: u32,
+    seamless: bool,
+) -> Vec<Point> {
+    let length = flattened_path_length(path, curve_tolerance);
 
-            true
-        },
-        interval,
+    // sample at normalized positions instead of stepping by a fixed interval so
+    // rounding can't add or drop a point
+    let steps = if seamless {
+        count
+    } else {
+        count.saturating_sub(1)
     };
+    let distances: Vec<f32> = (0..count)
+        .map(|i| {
+            if steps > 0 {
+                length * i as f32 / steps as f32
+            } else {
+                0f32
+            }
+        })
+        .collect();
 
-    walk_along_path(path.iter().flattened(curve_tolerance), 0f32, &mut pattern);
+    sample_path(path, curve_tolerance, &distances)
+}
+
+/// Measures a path along the same line segments `sample_path` walks, which is
+/// a little shorter than the curves themselves, so that every distance up to
+/// this length lands on the path.
+fn flattened_path_length(path: PathSlice, curve_tolerance: f32) -> f32 {
+    path.iter()
+        .flattened(curve_tolerance)
+        .map(|event| match event {
+            Event::Line { from, to } => (to - from).length(),
+            Event::End { last, first, close } if close => (first - last).length(),
+            _ => 0f32,
+        })
+        .sum()
+}
+
+/// Finds the points at each of the given distances along a path. The
+/// distances must be increasing. Distances past the end of the path are
+/// placed at its last point.
+fn sample_path(path: PathSlice, curve_tolerance: f32, distances: &[f32]) -> Vec<Point> {
+    let mut points = Vec::with_capacity(distances.len());
+    let mut distances = distances.iter().cloned().peekable();
+    let mut travelled = 0f32;
+    let mut last = None;
+
+    for event in path.iter().flattened(curve_tolerance) {
+        let (from, to) = match event {
+            Event::Begin { at } => {
+                last = Some(at);
+                continue;
+            }
+            Event::Line { from, to } => (from, to),
+            Event::End {
+                last, first, close, ..
+            } if close => (last, first),
+            _ => continue,
+        };
+
+        let segment_length = (to - from).length();
+        while let Some(&distance) = distances.peek() {
+            if distance > travelled + segment_length {
+                break;
+            }
+
+            let t = if segment_length > 0f32 {
+                (distance - travelled) / segment_length
+            } else {
+                0f32
+            };
+            points.push(from.lerp(to, t));
+            distances.next();
+        }
+
+        travelled += segment_length;
+        last = Some(to);
+    }
+
+    if let Some(last) = last {
+        points.extend(distances.map(|_| last));
+    }
 
     points
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quarter_circle_length_matches_arc() {
+        let radius = 10f32;
+        let k = QUARTER_CIRCLE_KAPPA;
+        let mut builder = Path::builder();
+        builder.move_to(point(radius, 0f32));
+        builder.cubic_bezier_to(
+            point(radius, k * radius),
+            point(k * radius, radius),
+            point(0f32, radius),
+        );
+        let path = builder.build();
+
+        let length = approximate_path_length(path.as_slice(), 0.001);
+        assert!((length - PI * radius / 2f32).abs() < 0.01, "{}", length);
+    }
+
+    #[test]
+    fn zero_length_path_repeats_its_point() {
+        let at = point(0.25f32, -0.5f32);
+        let count = 1_000_000;
+
+        for &seamless in &[false, true] {
+            let path = polyline_path(&[at, at]);
+            let points = path_points(path.as_slice(), 0.01, count, seamless);
+            assert_eq!(points.len(), count as usize);
+            assert!(points.iter().all(|&p| p == at));
+        }
+    }
+
+    #[test]
+    fn looped_circle_points_are_evenly_spaced() {
+        let path = circle_path(point(0f32, 0f32), 1f32);
+        let points = path_points(path.as_slice(), 0.01, 100, true);
+        assert_eq!(points.len(), 100);
+
+        // the gap from the last point back around to the first should be a
+        // step like any other, not a repeat of the start
+        let gaps: Vec<f32> = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(a, b)| (*b - *a).length())
+            .collect();
+        let mean = gaps.iter().sum::<f32>() / gaps.len() as f32;
+        for gap in gaps {
+            assert!((gap - mean).abs() < mean * 0.1, "{} vs {}", gap, mean);
+        }
+    }
+}
@@ -1,40 +1,410 @@
-use crate::generator::view::ConstrainedValue;
+use crate::generator::{
+    view::{ConstrainedValue, View},
+    RGBAColor,
+};
+use num_complex::Complex;
 use rusttype::{Font, Scale};
 
+/// How strongly the part of the path not yet reached is drawn over the image.
+const PATH_ALPHA: f32 = 0.3;
+
+/// How strongly the part of the path already traced is drawn over the image.
+const PATH_TRAIL_ALPHA: f32 = 0.9;
+
 // Draws a crosshair at the specified pixel location if within the constraint.
 pub fn draw_constrained_crosshair(
     image: &mut [u8],
     image_width: u32,
     image_height: u32,
     (pixel_x, pixel_y): (ConstrainedValue<u32>, ConstrainedValue<u32>),
+    color: RGBAColor,
+    thickness: u32,
 ) {
     if let ConstrainedValue::WithinConstraint(pixel_y) = pixel_y {
-        draw_horizontal_line(image, image_width, pixel_y);
+        draw_horizontal_line(image, image_width, image_height, pixel_y, color, thickness);
     }
     if let ConstrainedValue::WithinConstraint(pixel_x) = pixel_x {
-        draw_vertical_line(image, image_width, image_height, pixel_x);
+        draw_vertical_line(image, image_width, image_height, pixel_x, color, thickness);
+    }
+}
+
+/// Draws a crosshair at the specified subpixel location if within the
+/// constraint. The edges of the lines are blended into the neighboring pixels
+/// so the crosshair moves smoothly between frames.
+pub fn draw_constrained_antialiased_crosshair(
+    image: &mut [u8],
+    image_width: u32,
+    image_height: u32,
+    (pixel_x, pixel_y): (ConstrainedValue<f64>, ConstrainedValue<f64>),
+    color: RGBAColor,
+    thickness: u32,
+) {
+    if let ConstrainedValue::WithinConstraint(pixel_y) = pixel_y {
+        for (y, coverage) in antialiased_line_band(pixel_y, thickness, image_height) {
+            for x in 0..image_width {
+                blend_color(image, image_width, x, y, color, coverage);
+            }
+        }
+    }
+    if let ConstrainedValue::WithinConstraint(pixel_x) = pixel_x {
+        for (x, coverage) in antialiased_line_band(pixel_x, thickness, image_width) {
+            for y in 0..image_height {
+                blend_color(image, image_width, x, y, color, coverage);
+            }
+        }
+    }
+}
+
+/// Draws a filled circle with antialiased edges centered on the specified
+/// subpixel location. Nothing is drawn if the center is outside the image.
+pub fn draw_marker_dot(
+    image: &mut [u8],
+    image_width: u32,
+    image_height: u32,
+    (pixel_x, pixel_y): (ConstrainedValue<f64>, ConstrainedValue<f64>),
+    radius: f64,
+    color: RGBAColor,
+) {
+    let (center_x, center_y) = match (pixel_x, pixel_y) {
+        (ConstrainedValue::WithinConstraint(x), ConstrainedValue::WithinConstraint(y)) => (x, y),
+        _ => return,
+    };
+
+    let first_x = (center_x - radius - 1f64).floor().max(0f64) as u32;
+    let last_x = ((center_x + radius + 1f64).ceil() as u32).min(image_width);
+    let first_y = (center_y - radius - 1f64).floor().max(0f64) as u32;
+    let last_y = ((center_y + radius + 1f64).ceil() as u32).min(image_height);
+
+    for y in first_y..last_y {
+        for x in first_x..last_x {
+            // approximate coverage by how far the pixel's center is inside the edge
+            let distance = (x as f64 + 0.5f64 - center_x).hypot(y as f64 + 0.5f64 - center_y);
+            let coverage = (radius + 0.5f64 - distance).max(0f64).min(1f64);
+            if coverage > 0f64 {
+                blend_color(image, image_width, x, y, color, coverage as f32);
+            }
+        }
+    }
+}
+
+/// Gets the pixels touched by a line of the given thickness centered on a
+/// subpixel coordinate, clipped to the image, along with how much of each
+/// pixel the line covers.
+fn antialiased_line_band(
+    center: f64,
+    thickness: u32,
+    size: u32,
+) -> impl Iterator<Item = (u32, f32)> {
+    let half_thickness = thickness.max(1) as f64 / 2f64;
+    let (start, end) = (center - half_thickness, center + half_thickness);
+    let first = start.floor().max(0f64) as u32;
+    let last = (end.ceil().max(0f64) as u32).min(size);
+
+    (first..last).map(move |pixel| {
+        let coverage = end.min(pixel as f64 + 1f64) - start.max(pixel as f64);
+        (pixel, coverage.max(0f64) as f32)
+    })
+}
+
+/// Gets the range of pixels covered by a line of the given thickness centered
+/// on a coordinate, clipped to the image.
+fn line_band(center: u32, thickness: u32, size: u32) -> std::ops::Range<u32> {
+    let start = center.saturating_sub((thickness.max(1) - 1) / 2);
+    start..(start + thickness.max(1)).min(size)
+}
+
+/// Draws a vertical line across the image centered on the specified x
+/// coordinate.
+pub fn draw_vertical_line(
+    image: &mut [u8],
+    image_width: u32,
+    image_height: u32,
+    pixel_x: u32,
+    color: RGBAColor,
+    thickness: u32,
+) {
+    for x in line_band(pixel_x, thickness, image_width) {
+        for y in 0..image_height {
+            put_pixel(image, image_width, x, y, color);
+        }
+    }
+}
+
+/// Draws a horizontal line across the image centered on the specified y
+/// coordinate.
+pub fn draw_horizontal_line(
+    image: &mut [u8],
+    image_width: u32,
+    image_height: u32,
+    pixel_y: u32,
+    color: RGBAColor,
+    thickness: u32,
+) {
+    for y in line_band(pixel_y, thickness, image_height) {
+        for x in 0..image_width {
+            put_pixel(image, image_width, x, y, color);
+        }
+    }
+}
+
+/// Draws the outline of a rectangle between two constrained corners. Edges
+/// whose coordinates fall outside the image are not drawn.
+pub fn draw_constrained_rectangle(
+    image: &mut [u8],
+    image_width: u32,
+    image_height: u32,
+    (left, top): (ConstrainedValue<u32>, ConstrainedValue<u32>),
+    (right, bottom): (ConstrainedValue<u32>, ConstrainedValue<u32>),
+) {
+    if image_width == 0 || image_height == 0 {
+        return;
+    }
+
+    let clamp_x = |v: ConstrainedValue<u32>| match v {
+        ConstrainedValue::LessThanConstraint => 0,
+        ConstrainedValue::WithinConstraint(v) => v,
+        ConstrainedValue::GreaterThanConstraint => image_width - 1,
+    };
+    let clamp_y = |v: ConstrainedValue<u32>| match v {
+        ConstrainedValue::LessThanConstraint => 0,
+        ConstrainedValue::WithinConstraint(v) => v,
+        ConstrainedValue::GreaterThanConstraint => image_height - 1,
+    };
+
+    let (min_x, max_x) = (clamp_x(left), clamp_x(right));
+    let (min_y, max_y) = (clamp_y(top), clamp_y(bottom));
+
+    for &edge in [top, bottom].iter() {
+        if let ConstrainedValue::WithinConstraint(y) = edge {
+            for x in min_x..=max_x {
+                set_pixel(image, image_width, x, y);
+            }
+        }
+    }
+    for &edge in [left, right].iter() {
+        if let ConstrainedValue::WithinConstraint(x) = edge {
+            for y in min_y..=max_y {
+                set_pixel(image, image_width, x, y);
+            }
+        }
+    }
+}
+
+/// Draws the path of points on the plane over the image, with the whole path
+/// drawn faintly and the part up to and including the current point drawn
+/// brightly. Consecutive points within the image are joined by lines.
+pub fn draw_path_overlay(image: &mut [u8], view: &View, points: &[Complex<f64>], current: usize) {
+    let mut previous = None;
+
+    for (index, &point) in points.iter().enumerate() {
+        let alpha = if index <= current {
+            PATH_TRAIL_ALPHA
+        } else {
+            PATH_ALPHA
+        };
+
+        previous = match view.get_pixel_coordinates(point) {
+            (ConstrainedValue::WithinConstraint(x), ConstrainedValue::WithinConstraint(y)) => {
+                let (start_x, start_y) = previous.unwrap_or((x, y));
+                let steps = (x as i64 - start_x as i64)
+                    .abs()
+                    .max((y as i64 - start_y as i64).abs())
+                    .max(1);
+
+                for step in 1..=steps {
+                    let t = step as f64 / steps as f64;
+                    let line_x = start_x as f64 + (x as f64 - start_x as f64) * t;
+                    let line_y = start_y as f64 + (y as f64 - start_y as f64) * t;
+                    blend_pixel(
+                        image,
+                        view.image_width,
+                        line_x.round() as u32,
+                        line_y.round() as u32,
+                        alpha,
+                    );
+                }
+
+                Some((x, y))
+            }
+            _ => None,
+        };
+    }
+}
+
+/// Draws lines in a color between each consecutive pair of points on the
+/// plane, leaving out any line with an end outside the image.
+pub fn draw_polyline(image: &mut [u8], view: &View, points: &[Complex<f64>], color: RGBAColor) {
+    let mut previous = None;
+
+    for &point in points {
+        previous = match view.get_pixel_coordinates(point) {
+            (ConstrainedValue::WithinConstraint(x), ConstrainedValue::WithinConstraint(y)) => {
+                let (start_x, start_y) = previous.unwrap_or((x, y));
+                let steps = (x as i64 - start_x as i64)
+                    .abs()
+                    .max((y as i64 - start_y as i64).abs())
+                    .max(1);
+
+                for step in 0..=steps {
+                    let t = step as f64 / steps as f64;
+                    let line_x = start_x as f64 + (x as f64 - start_x as f64) * t;
+                    let line_y = start_y as f64 + (y as f64 - start_y as f64) * t;
+                    put_pixel(
+                        image,
+                        view.image_width,
+                        line_x.round() as u32,
+                        line_y.round() as u32,
+                        color,
+                    );
+                }
+
+                Some((x, y))
+            }
+            _ => None,
+        };
     }
 }
 
-/// Draws a vertical line across the image at the specified x coordinate.
-pub fn draw_vertical_line(image: &mut [u8], image_width: u32, image_height: u32, pixel_x: u32) {
-    for y in 0..image_height as usize {
-        let index = (y * image_width as usize + pixel_x as usize) * 4;
-        image[index] = 0xFFu8;
-        image[index + 1] = 0xFFu8;
-        image[index + 2] = 0xFFu8;
-        image[index + 3] = 0xFFu8;
+/// Blends a single pixel of the image towards white.
+fn blend_pixel(image: &mut [u8], image_width: u32, pixel_x: u32, pixel_y: u32, alpha: f32) {
+    let index = (pixel_y as usize * image_width as usize + pixel_x as usize) * 4;
+    for channel in &mut image[index..index + 3] {
+        *channel += ((0xFF - *channel) as f32 * alpha) as u8;
     }
+    image[index + 3] = 0xFFu8;
 }
 
-/// Draws a horizontal line across the image at the specified y coordinate.
-pub fn draw_horizontal_line(image: &mut [u8], image_width: u32, pixel_y: u32) {
-    for x in 0..image_width as usize {
-        let index = (pixel_y as usize * image_width as usize + x) * 4;
-        image[index] = 0xFFu8;
-        image[index + 1] = 0xFFu8;
-        image[index + 2] = 0xFFu8;
-        image[index + 3] = 0xFFu8;
+/// Blends a color over a single pixel of the image, weighted by how much of
+/// the pixel it covers.
+fn blend_color(
+    image: &mut [u8],
+    image_width: u32,
+    pixel_x: u32,
+    pixel_y: u32,
+    color: RGBAColor,
+    coverage: f32,
+) {
+    let alpha = coverage * color.a as f32 / 255f32;
+    let blend = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * alpha).round() as u8;
+
+    let index = (pixel_y as usize * image_width as usize + pixel_x as usize) * 4;
+    image[index] = blend(image[index], color.r);
+    image[index + 1] = blend(image[index + 1], color.g);
+    image[index + 2] = blend(image[index + 2], color.b);
+    image[index + 3] = blend(image[index + 3], 0xFF);
+}
+
+/// Composites a color over a single pixel with straight (not premultiplied)
+/// alpha, using the source-over operator so pixels that are already partly
+/// transparent keep a correct alpha.
+fn composite_over(pixel: &mut [u8], color: RGBAColor, coverage: f32) {
+    let source_alpha = (coverage * color.a as f32 / 255f32).max(0f32).min(1f32);
+    let dest_alpha = pixel[3] as f32 / 255f32;
+    let out_alpha = source_alpha + dest_alpha * (1f32 - source_alpha);
+
+    if out_alpha <= 0f32 {
+        pixel.copy_from_slice(&[0, 0, 0, 0]);
+        return;
+    }
+
+    let composite = |source: u8, dest: u8| {
+        let value = (source as f32 * source_alpha
+            + dest as f32 * dest_alpha * (1f32 - source_alpha))
+            / out_alpha;
+        value.round().max(0f32).min(255f32) as u8
+    };
+
+    pixel[0] = composite(color.r, pixel[0]);
+    pixel[1] = composite(color.g, pixel[1]);
+    pixel[2] = composite(color.b, pixel[2]);
+    pixel[3] = (out_alpha * 255f32).round().min(255f32) as u8;
+}
+
+/// Sets a single pixel of the image to white.
+fn set_pixel(image: &mut [u8], image_width: u32, pixel_x: u32, pixel_y: u32) {
+    put_pixel(
+        image,
+        image_width,
+        pixel_x,
+        pixel_y,
+        RGBAColor::new(0xFF, 0xFF, 0xFF, 0xFF),
+    );
+}
+
+/// Sets a single pixel of the image to a color.
+fn put_pixel(image: &mut [u8], image_width: u32, pixel_x: u32, pixel_y: u32, color: RGBAColor) {
+    let index = (pixel_y as usize * image_width as usize + pixel_x as usize) * 4;
+    image[index] = color.r;
+    image[index + 1] = color.g;
+    image[index + 2] = color.b;
+    image[index + 3] = color.a;
+}
+
+/// Shrinks an RGBA image by an integer factor, averaging each `factor` by
+/// `factor` block of source pixels into a single pixel of the image.
+pub fn downsample(
+    source: &[u8],
+    source_width: u32,
+    image: &mut [u8],
+    image_width: u32,
+    image_height: u32,
+    factor: u32,
+) {
+    let block_size = factor * factor;
+
+    for y in 0..image_height {
+        for x in 0..image_width {
+            let mut sums = [0u32; 4];
+            for source_y in y * factor..(y + 1) * factor {
+                for source_x in x * factor..(x + 1) * factor {
+                    let index = (source_y as usize * source_width as usize + source_x as usize) * 4;
+                    for (sum, &channel) in sums.iter_mut().zip(&source[index..index + 4]) {
+                        *sum += channel as u32;
+                    }
+                }
+            }
+
+            let index = (y as usize * image_width as usize + x as usize) * 4;
+            for (channel, sum) in image[index..index + 4].iter_mut().zip(&sums) {
+                *channel = ((sum + block_size / 2) / block_size) as u8;
+            }
+        }
+    }
+}
+
+/// Scales the color channels of an RGBA image by a factor between 0 and 1,
+/// fading it towards black while leaving its alpha alone.
+pub fn fade_image(image: &mut [u8], factor: f64) {
+    for pixel in image.chunks_exact_mut(4) {
+        for channel in &mut pixel[..3] {
+            *channel = (*channel as f64 * factor).round() as u8;
+        }
+    }
+}
+
+/// Copies a source image onto the image with its top-left corner at the
+/// specified location, clipping anything that falls outside the image.
+pub fn draw_image(
+    image: &mut [u8],
+    image_width: u32,
+    image_height: u32,
+    source: &[u8],
+    source_width: u32,
+    source_height: u32,
+    (x, y): (u32, u32),
+) {
+    if x >= image_width || y >= image_height {
+        return;
+    }
+
+    let copy_width = source_width.min(image_width - x) as usize;
+    let copy_height = source_height.min(image_height - y) as usize;
+
+    for row in 0..copy_height {
+        let source_index = row * source_width as usize * 4;
+        let index = ((y as usize + row) * image_width as usize + x as usize) * 4;
+        image[index..index + copy_width * 4]
+            .copy_from_slice(&source[source_index..source_index + copy_width * 4]);
     }
 }
 
@@ -107,22 +477,55 @@ pub fn draw_glyph_line(
     ) {
         if let Some(bounding_box) = glyph.pixel_bounding_box() {
             glyph.draw(|x, y, c| {
-                let pixel_x = x + bounding_box.min.x as u32;
-                let pixel_y = y + bounding_box.min.y as u32;
-                if pixel_x < image_width && pixel_y < image_height {
-                    let index = ((pixel_y * image_width + pixel_x) * 4) as usize;
-                    let value = (255f32 * c) as u8;
-                    let back = 1f32 - c;
-                    image[index] = value + (back * image[index] as f32) as u8;
-                    image[index + 1] = value + (back * image[index + 1] as f32) as u8;
-                    image[index + 2] = value + (back * image[index + 2] as f32) as u8;
-                    image[index + 3] = value + (back * image[index + 3] as f32) as u8;
+                // glyphs can extend past the pen position, so the bounding box
+                // may start at negative coordinates
+                let pixel_x = x as i32 + bounding_box.min.x;
+                let pixel_y = y as i32 + bounding_box.min.y;
+                if pixel_x >= 0
+                    && pixel_y >= 0
+                    && (pixel_x as u32) < image_width
+                    && (pixel_y as u32) < image_height
+                {
+                    let index = (pixel_y as usize * image_width as usize + pixel_x as usize) * 4;
+                    composite_over(
+                        &mut image[index..index + 4],
+                        RGBAColor::new(0xFF, 0xFF, 0xFF, 0xFF),
+                        c,
+                    );
                 }
             });
         }
     }
 }
 
+/// Draws several lines of glyphs stacked top to bottom onto the image buffer.
+pub fn draw_glyph_block(
+    image: &mut [u8],
+    image_width: u32,
+    image_height: u32,
+    font: &Font,
+    scale: Scale,
+    (x, y): (u32, u32),
+    margin: f32,
+    lines: &[String],
+) {
+    let v_metrics = font.v_metrics(scale);
+    let line_height = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
+
+    for (index, line) in lines.iter().enumerate() {
+        draw_glyph_line(
+            image,
+            image_width,
+            image_height,
+            font,
+            scale,
+            (x, y + (index as f32 * line_height) as u32),
+            margin,
+            line,
+        );
+    }
+}
+
 /// Gets the dimensions of a single line of glyphs
 pub fn get_glyph_line_dimensions(
     font: &Font,
@@ -130,16 +533,16 @@ pub fn get_glyph_line_dimensions(
     margin: f32,
     string: &str,
 ) -> (f32, f32) {
-    let str_len = string.len();
     let mut width = margin * 2f32;
     let mut last = None;
+    let mut glyphs = font.glyphs_for(string.chars()).peekable();
 
-    for (index, glyph) in font.glyphs_for(string.chars()).enumerate() {
+    while let Some(glyph) = glyphs.next() {
         let glyph = glyph.scaled(scale);
         if let Some(last) = last {
             width += font.pair_kerning(scale, last, glyph.id());
         }
-        if index < str_len - 1 {
+        if glyphs.peek().is_some() {
             width += glyph.h_metrics().advance_width;
         } else {
             width += glyph.h_metrics().left_side_bearing;
@@ -151,3 +554,62 @@ pub fn get_glyph_line_dimensions(
 
     (width, v_metrics.ascent - v_metrics.descent + margin * 2f32)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_font() -> Font<'static> {
+        Font::from_bytes(&include_bytes!("OxygenMono-Regular.ttf")[..]).unwrap()
+    }
+
+    #[test]
+    fn empty_glyph_line_is_only_margins() {
+        let font = test_font();
+        let scale = Scale::uniform(16f32);
+        let v_metrics = font.v_metrics(scale);
+
+        assert_eq!(
+            get_glyph_line_dimensions(&font, scale, 3f32, ""),
+            (6f32, v_metrics.ascent - v_metrics.descent + 6f32)
+        );
+    }
+
+    #[test]
+    fn glyphs_past_the_left_edge_are_clipped() {
+        let font = test_font();
+        let scale = Scale::uniform(16f32);
+        let margin = -4f32;
+
+        // a negative margin pushes the first glyph partly off the image
+        let ascent = font.v_metrics(scale).ascent;
+        let first = font
+            .layout("W", scale, rusttype::point(margin, margin + ascent))
+            .next()
+            .and_then(|glyph| glyph.pixel_bounding_box())
+            .unwrap();
+        assert!(first.min.x < 0);
+
+        let mut image = vec![0u8; 16 * 16 * 4];
+        draw_glyph_line(&mut image, 16, 16, &font, scale, (0, 0), margin, "WW");
+
+        assert!(image.chunks(4).any(|pixel| pixel[3] > 0));
+    }
+
+    #[test]
+    fn composite_over_blends_with_translucent_pixels() {
+        let white = RGBAColor::new(0xFF, 0xFF, 0xFF, 0xFF);
+
+        let mut pixel = [200u8, 0, 100, 128];
+        composite_over(&mut pixel, white, 0.25);
+        assert_eq!(pixel, [222, 102, 162, 160]);
+
+        let mut pixel = [200u8, 0, 100, 128];
+        composite_over(&mut pixel, white, 0f32);
+        assert_eq!(pixel, [200, 0, 100, 128]);
+
+        let mut pixel = [200u8, 0, 100, 128];
+        composite_over(&mut pixel, white, 1f32);
+        assert_eq!(pixel, [255, 255, 255, 255]);
+    }
+}
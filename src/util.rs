@@ -1,26 +1,168 @@
 use ffmpeg4::Rational;
+use julia_in_motion::generator::RGBAColor;
+use num_complex::Complex;
 use regex::Regex;
-use std::num::ParseIntError;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    num::{ParseFloatError, ParseIntError},
+};
 
 lazy_static::lazy_static! {
     static ref RATIONAL_REGEX: Regex = Regex::new(r"^(\d+)/(\d+)$").unwrap();
+    static ref DECIMAL_REGEX: Regex = Regex::new(r"^(\d*)\.(\d+)$|^(\d+)\.?$").unwrap();
+    static ref COMPONENT_REGEX: Regex = Regex::new(r"\{(re|im)(?::\.(\d+))?\}").unwrap();
 }
 
+/// Common NTSC rates that are usually written as decimals but are actually
+/// `n * 1000 / 1001`.
+const NTSC_RATES: &[(&str, i32)] = &[
+    ("23.976", 24000),
+    ("29.97", 30000),
+    ("47.952", 48000),
+    ("59.94", 60000),
+    ("119.88", 120000),
+];
+
+/// Parses a rational from either a `numerator/denominator` fraction or a
+/// decimal number. Decimal NTSC rates like `29.97` are converted to their
+/// exact `30000/1001` form.
 pub fn parse_rational(string: &str) -> Result<Rational, ParseRationalError> {
     if let Some(captures) = RATIONAL_REGEX.captures(string) {
         Ok(Rational::new(
             captures[1].parse::<i32>()?,
             captures[2].parse::<i32>()?,
         ))
+    } else if let Some(&(_, numerator)) = NTSC_RATES.iter().find(|(rate, _)| *rate == string) {
+        Ok(Rational::new(numerator, 1001))
+    } else if let Some(captures) = DECIMAL_REGEX.captures(string) {
+        let (whole, fraction) = match captures.get(3) {
+            Some(whole) => (whole.as_str(), ""),
+            None => (&captures[1], &captures[2]),
+        };
+
+        let denominator = 10i64
+            .checked_pow(fraction.len() as u32)
+            .ok_or(ParseRationalError::OutOfRange)?;
+        let whole = if whole.is_empty() {
+            0
+        } else {
+            whole.parse::<i64>()?
+        };
+        let fraction = if fraction.is_empty() {
+            0
+        } else {
+            fraction.parse::<i64>()?
+        };
+        let numerator = whole
+            .checked_mul(denominator)
+            .and_then(|n| n.checked_add(fraction))
+            .ok_or(ParseRationalError::OutOfRange)?;
+
+        let divisor = gcd(numerator, denominator);
+        let numerator = numerator / divisor;
+        let denominator = denominator / divisor;
+
+        if numerator > i32::max_value() as i64 || denominator > i32::max_value() as i64 {
+            return Err(ParseRationalError::OutOfRange);
+        }
+
+        Ok(Rational::new(numerator as i32, denominator as i32))
     } else {
         Err(ParseRationalError::NotARational)
     }
 }
 
+/// Parses a complex number written as `real,imaginary`.
+pub fn parse_complex(string: &str) -> Result<Complex<f64>, ParseComplexError> {
+    let mut components = string.splitn(2, ',');
+    let re = components.next().unwrap_or("").trim().parse::<f64>()?;
+    let im = components
+        .next()
+        .ok_or(ParseComplexError::MissingImaginary)?
+        .trim()
+        .parse::<f64>()?;
+
+    Ok(Complex::<f64>::new(re, im))
+}
+
+/// Formats a complex number using a template where `{re}` and `{im}` are
+/// replaced by its components. A precision can be given like `{re:.5}`.
+pub fn format_complex(template: &str, value: Complex<f64>) -> String {
+    COMPONENT_REGEX
+        .replace_all(template, |captures: &regex::Captures| {
+            let component = if &captures[1] == "re" {
+                value.re
+            } else {
+                value.im
+            };
+
+            match captures
+                .get(2)
+                .and_then(|p| p.as_str().parse::<usize>().ok())
+            {
+                Some(precision) => format!("{:.*}", precision, component),
+                None => component.to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Parses a color written in hexadecimal as `RRGGBB` or `RRGGBBAA`, with an
+/// optional leading `#`. Colors without an alpha component are opaque.
+pub fn parse_color(string: &str) -> Result<RGBAColor, ParseColorError> {
+    let digits = string.trim().trim_start_matches('#');
+    if !digits.is_ascii() || (digits.len() != 6 && digits.len() != 8) {
+        return Err(ParseColorError::InvalidLength);
+    }
+
+    let channel = |index: usize| u8::from_str_radix(&digits[index..index + 2], 16);
+    let alpha = if digits.len() == 8 {
+        channel(6)?
+    } else {
+        0xFFu8
+    };
+
+    Ok(RGBAColor::new(channel(0)?, channel(2)?, channel(4)?, alpha))
+}
+
+/// Finds the greatest common divisor of two non-negative numbers.
+fn gcd(mut a: i64, mut b: i64) -> i64 {
+    while b != 0 {
+        let t = b;
+        b = a % b;
+        a = t;
+    }
+
+    a
+}
+
 #[derive(Debug, Clone)]
 pub enum ParseRationalError {
     NotARational,
     InvalidRationalComponent(ParseIntError),
+    OutOfRange,
+}
+
+impl Display for ParseRationalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseRationalError::NotARational => {
+                write!(f, "expected a fraction like 30000/1001 or a decimal")
+            }
+            ParseRationalError::InvalidRationalComponent(e) => e.fmt(f),
+            ParseRationalError::OutOfRange => write!(f, "the value is too large or too precise"),
+        }
+    }
+}
+
+impl Error for ParseRationalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseRationalError::InvalidRationalComponent(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 impl From<ParseIntError> for ParseRationalError {
@@ -28,3 +170,98 @@ impl From<ParseIntError> for ParseRationalError {
         ParseRationalError::InvalidRationalComponent(e)
     }
 }
+
+#[derive(Debug, Clone)]
+pub enum ParseComplexError {
+    MissingImaginary,
+    InvalidComplexComponent(ParseFloatError),
+}
+
+impl Display for ParseComplexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseComplexError::MissingImaginary => {
+                write!(f, "expected a complex number like real,imaginary")
+            }
+            ParseComplexError::InvalidComplexComponent(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for ParseComplexError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseComplexError::MissingImaginary => None,
+            ParseComplexError::InvalidComplexComponent(e) => Some(e),
+        }
+    }
+}
+
+impl From<ParseFloatError> for ParseComplexError {
+    fn from(e: ParseFloatError) -> Self {
+        ParseComplexError::InvalidComplexComponent(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseColorError {
+    InvalidLength,
+    InvalidColorComponent(ParseIntError),
+}
+
+impl Display for ParseColorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseColorError::InvalidLength => write!(f, "expected a color like RRGGBB or RRGGBBAA"),
+            ParseColorError::InvalidColorComponent(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for ParseColorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseColorError::InvalidLength => None,
+            ParseColorError::InvalidColorComponent(e) => Some(e),
+        }
+    }
+}
+
+impl From<ParseIntError> for ParseColorError {
+    fn from(e: ParseIntError) -> Self {
+        ParseColorError::InvalidColorComponent(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ntsc_decimal_matches_fraction() {
+        assert_eq!(
+            parse_rational("29.97").unwrap(),
+            parse_rational("30000/1001").unwrap()
+        );
+        assert_eq!(parse_rational("29.97").unwrap(), Rational::new(30000, 1001));
+    }
+
+    #[test]
+    fn decimal_is_reduced() {
+        assert_eq!(parse_rational("0.1").unwrap(), Rational::new(1, 10));
+        assert_eq!(parse_rational("2.5").unwrap(), Rational::new(5, 2));
+        assert_eq!(parse_rational("30").unwrap(), Rational::new(30, 1));
+    }
+
+    #[test]
+    fn overflowing_decimal_is_out_of_range() {
+        assert!(matches!(
+            parse_rational("0.123456789012345678901"),
+            Err(ParseRationalError::OutOfRange)
+        ));
+        assert!(matches!(
+            parse_rational("9999999999.5"),
+            Err(ParseRationalError::OutOfRange)
+        ));
+    }
+}